@@ -4,9 +4,31 @@ use std::time::Duration;
 
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 
-use solver::solver::backtrack::backtrack;
+use solver::solver::backtrack::BacktrackConfig;
+use solver::solver::{solver_by_name, Solver, SolverConfig};
 use solver::web::get_levels;
 
+const SOLVER: &str = "backtrack";
+
+/// The default parameters, overridden by `name=value` pairs in the
+/// `BACKTRACK_TUNE` environment variable.
+fn config() -> SolverConfig {
+    let backtrack = std::env::var("BACKTRACK_TUNE")
+        .map(|tune| {
+            tune.parse::<BacktrackConfig>()
+                .expect("invalid BACKTRACK_TUNE")
+        })
+        .unwrap_or_default();
+    SolverConfig {
+        backtrack,
+        ..SolverConfig::default()
+    }
+}
+
+fn solve(solver: &dyn Solver, puzzle: &solver::game::puzzle::Puzzle) -> usize {
+    solver.solve(puzzle, &config()).solutions.len()
+}
+
 fn bench_backtrack_easy_puzzles(c: &mut Criterion) {
     let benchmark_group_name = "Easy puzzles 1.0";
     let puzzle_ids = &[123, 27, 46, 45, 220, 24, 52, 264, 147, 222, 68, 202, 138];
@@ -66,13 +88,14 @@ fn fun_name(
     puzzle_ids: &[u64],
     mut group: criterion::BenchmarkGroup<criterion::measurement::WallTime>,
 ) {
+    let solver = solver_by_name(SOLVER).expect("unknown solver");
     let levels: Vec<_> = get_levels(puzzle_ids.into_iter().map(|e| *e))
         .map(Result::unwrap)
         .collect();
     for (i, level) in levels.iter().enumerate() {
         group.bench_with_input(BenchmarkId::new("level", i), &level.puzzle, |b, puzzle| {
             b.iter(|| {
-                assert!(backtrack(black_box(*puzzle), None).len() > 0);
+                assert!(solve(solver.as_ref(), black_box(puzzle)) > 0);
             })
         });
     }
@@ -82,6 +105,7 @@ fn fun_name(
 fn backtrack_batches(c: &mut Criterion) {
     let benchmark_id = "Easy batch";
     let puzzle_ids = &[123, 27, 46, 45, 220, 24, 52, 264, 147, 222, 68, 202, 138];
+    let solver = solver_by_name(SOLVER).expect("unknown solver");
     let mut group = c.benchmark_group("Batches");
     group.warm_up_time(Duration::from_millis(500));
     group.sample_size(100);
@@ -94,7 +118,7 @@ fn backtrack_batches(c: &mut Criterion) {
     group.bench_with_input(benchmark_id, &levels, |b, levels| {
         b.iter(|| {
             for (_i, level) in levels.iter().enumerate() {
-                assert!(backtrack(black_box(level.puzzle), None).len() > 0);
+                assert!(solve(solver.as_ref(), black_box(&level.puzzle)) > 0);
             }
         })
     });
@@ -117,7 +141,7 @@ fn backtrack_batches(c: &mut Criterion) {
     group.bench_with_input(benchmark_id, &levels, |b, levels| {
         b.iter(|| {
             for (_i, level) in levels.iter().enumerate() {
-                assert!(backtrack(black_box(level.puzzle), None).len() > 0);
+                assert!(solve(solver.as_ref(), black_box(&level.puzzle)) > 0);
             }
         })
     });
@@ -139,7 +163,7 @@ fn backtrack_batches(c: &mut Criterion) {
     group.bench_with_input(benchmark_id, &levels, |b, levels| {
         b.iter(|| {
             for (_i, level) in levels.iter().enumerate() {
-                assert!(backtrack(black_box(level.puzzle), None).len() > 0);
+                assert!(solve(solver.as_ref(), black_box(&level.puzzle)) > 0);
             }
         })
     });
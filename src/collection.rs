@@ -0,0 +1,451 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use serde::Serialize;
+use statrs::statistics::Statistics;
+
+use crate::game::{state, Source};
+use crate::solver::solutions::{read_solution_from_file, read_stats_from_file};
+use crate::solver::SolverStats;
+use crate::web::Level;
+
+/// Structural features of a puzzle next to its community ratings and what
+/// the caches know about solving it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PuzzleFeatures {
+    pub id: u64,
+    pub title: String,
+    /// Tiles the robot can stand on.
+    pub tiles: usize,
+    pub stars: usize,
+    pub colors: usize,
+    pub marks: usize,
+    pub methods: usize,
+    /// Instruction slots of all methods together.
+    pub slots: usize,
+    pub likes: u64,
+    pub difficulty_votes: u64,
+    /// The mean difficulty vote, see [`Level::difficulty`].
+    pub difficulty: Option<f64>,
+    /// Steps of the fastest cached solution.
+    pub solution_steps: Option<usize>,
+    /// Instructions of the shortest cached solution.
+    pub solution_instructions: Option<usize>,
+    /// Branches of the last cached search.
+    pub branches: Option<u64>,
+    /// Steps executed by the last cached search.
+    pub search_steps: Option<u64>,
+}
+
+impl PuzzleFeatures {
+    pub fn new(level: &Level, solutions: &[Source], stats: Option<&SolverStats>) -> Self {
+        let puzzle = &level.puzzle;
+        PuzzleFeatures {
+            id: level.id,
+            title: level.title.clone(),
+            tiles: puzzle.board.count_tiles(),
+            stars: puzzle.stars,
+            colors: puzzle.color_count(),
+            marks: puzzle.mark_count(),
+            methods: puzzle.method_count(),
+            slots: puzzle.slot_count(),
+            likes: level.likes,
+            difficulty_votes: level.difficulty_vote_count,
+            difficulty: level.difficulty(),
+            solution_steps: solutions
+                .iter()
+                .map(|solution| puzzle.execute(solution, false, state::steps))
+                .min(),
+            solution_instructions: solutions.iter().map(Source::count_ins).min(),
+            branches: stats.map(|stats| stats.branches),
+            search_steps: stats.map(|stats| stats.steps),
+        }
+    }
+    /// The features of a level with the solutions and search stats cached in
+    /// `data/solutions` and `data/stats`.
+    pub fn from_caches(level: &Level) -> Self {
+        let solutions = read_solution_from_file(level.id).unwrap_or_default();
+        let stats = read_stats_from_file(level.id).ok();
+        PuzzleFeatures::new(level, &solutions, stats.as_ref())
+    }
+    pub fn solved(&self) -> bool {
+        self.solution_steps.is_some()
+    }
+    /// The features that are correlated with the difficulty, by name.
+    pub fn numeric(&self) -> [(&'static str, Option<f64>); 12] {
+        [
+            ("tiles", Some(self.tiles as f64)),
+            ("stars", Some(self.stars as f64)),
+            ("colors", Some(self.colors as f64)),
+            ("marks", Some(self.marks as f64)),
+            ("methods", Some(self.methods as f64)),
+            ("slots", Some(self.slots as f64)),
+            ("likes", Some(self.likes as f64)),
+            ("solved", Some(self.solved() as u8 as f64)),
+            (
+                "solution_steps",
+                self.solution_steps.map(|steps| steps as f64),
+            ),
+            (
+                "solution_instructions",
+                self.solution_instructions.map(|count| count as f64),
+            ),
+            ("branches", self.branches.map(|branches| branches as f64)),
+            ("search_steps", self.search_steps.map(|steps| steps as f64)),
+        ]
+    }
+}
+
+/// How a feature goes along with the difficulty votes over the puzzles that
+/// have both, `None` when there are too few of them or either is constant.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Correlation {
+    pub feature: &'static str,
+    pub samples: usize,
+    pub pearson: Option<f64>,
+    /// The Pearson correlation of the ranks, robust to the skew of counts
+    /// such as branches.
+    pub spearman: Option<f64>,
+}
+
+pub fn correlations(features: &[PuzzleFeatures]) -> Vec<Correlation> {
+    let names = features.first().map_or(vec![], |first| {
+        first.numeric().iter().map(|&(name, _)| name).collect()
+    });
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(index, feature)| {
+            let (values, difficulties): (Vec<f64>, Vec<f64>) = features
+                .iter()
+                .filter_map(|puzzle| Some((puzzle.numeric()[index].1?, puzzle.difficulty?)))
+                .unzip();
+            Correlation {
+                feature,
+                samples: values.len(),
+                pearson: pearson(&values, &difficulties),
+                spearman: pearson(&ranks(&values), &ranks(&difficulties)),
+            }
+        })
+        .collect()
+}
+
+fn pearson(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    if xs.len() < 3 {
+        return None;
+    }
+    let correlation = xs.covariance(ys) / (xs.std_dev() * ys.std_dev());
+    correlation.is_finite().then_some(correlation)
+}
+
+/// The rank of every value, ties get the mean of their ranks.
+fn ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+    let mut result = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        for &index in &order[start..end] {
+            result[index] = (start + end - 1) as f64 / 2.0;
+        }
+        start = end;
+    }
+    result
+}
+
+/// Every field of the features, a header and a line per puzzle.
+pub fn features_csv(features: &[PuzzleFeatures]) -> String {
+    let mut result = "id,title,tiles,stars,colors,marks,methods,slots,likes,difficulty_votes,\
+                      difficulty,solution_steps,solution_instructions,branches,search_steps\n"
+        .to_string();
+    for puzzle in features {
+        writeln!(
+            result,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            puzzle.id,
+            csv_text(&puzzle.title),
+            puzzle.tiles,
+            puzzle.stars,
+            puzzle.colors,
+            puzzle.marks,
+            puzzle.methods,
+            puzzle.slots,
+            puzzle.likes,
+            puzzle.difficulty_votes,
+            csv_option(puzzle.difficulty),
+            csv_option(puzzle.solution_steps),
+            csv_option(puzzle.solution_instructions),
+            csv_option(puzzle.branches),
+            csv_option(puzzle.search_steps),
+        )
+        .expect("writing to a string");
+    }
+    result
+}
+
+pub fn correlations_csv(correlations: &[Correlation]) -> String {
+    let mut result = "feature,samples,pearson,spearman\n".to_string();
+    for correlation in correlations {
+        writeln!(
+            result,
+            "{},{},{},{}",
+            correlation.feature,
+            correlation.samples,
+            csv_option(correlation.pearson),
+            csv_option(correlation.spearman),
+        )
+        .expect("writing to a string");
+    }
+    result
+}
+
+fn csv_option<T: ToString>(value: Option<T>) -> String {
+    value.map_or(String::new(), |value| value.to_string())
+}
+
+fn csv_text(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Inclusive bounds on an id or a count, parsed from `N`, `A..B`, `A..` or
+/// `..B`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Bounds {
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+}
+
+impl Bounds {
+    pub fn contains(&self, value: u64) -> bool {
+        self.min.is_none_or(|min| min <= value) && self.max.is_none_or(|max| value <= max)
+    }
+}
+
+impl FromStr for Bounds {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let bound = |text: &str| -> Result<Option<u64>, String> {
+            match text.trim() {
+                "" => Ok(None),
+                number => number
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| format!("'{}' is not a number", number)),
+            }
+        };
+        let bounds = match text.split_once("..") {
+            Some((min, max)) => Bounds {
+                min: bound(min)?,
+                max: bound(max.strip_prefix('=').unwrap_or(max))?,
+            },
+            None => {
+                let exact = bound(text)?.ok_or("expected a number or a range")?;
+                Bounds {
+                    min: Some(exact),
+                    max: Some(exact),
+                }
+            }
+        };
+        Ok(bounds)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Id,
+    Title,
+    Difficulty,
+    Likes,
+    Slots,
+    Stars,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Ok(match text {
+            "id" => SortKey::Id,
+            "title" => SortKey::Title,
+            "difficulty" => SortKey::Difficulty,
+            "likes" => SortKey::Likes,
+            "slots" => SortKey::Slots,
+            "stars" => SortKey::Stars,
+            _ => return Err(format!("unknown sort key '{}'", text)),
+        })
+    }
+}
+
+/// Filters and an order for the levels of the local collection. Text filters
+/// match case-insensitive substrings, `None` and unbounded fields match
+/// every level.
+#[derive(Clone, Debug, Default)]
+pub struct PuzzleQuery {
+    pub ids: Bounds,
+    pub title: Option<String>,
+    /// Matched against [`Level::submitted_by`].
+    pub author: Option<String>,
+    pub featured: Option<bool>,
+    pub colors: Bounds,
+    pub marks: Bounds,
+    pub slots: Bounds,
+    /// Whether solutions are cached in `data/solutions`.
+    pub solved: Option<bool>,
+    /// Levels are in collection order without a key.
+    pub sort: Option<SortKey>,
+    pub descending: bool,
+}
+
+impl PuzzleQuery {
+    pub fn matches(&self, level: &Level, solved: impl Fn(u64) -> bool) -> bool {
+        let contains = |text: &str, part: &Option<String>| {
+            part.as_ref()
+                .is_none_or(|part| text.to_lowercase().contains(&part.to_lowercase()))
+        };
+        let puzzle = &level.puzzle;
+        self.ids.contains(level.id)
+            && contains(&level.title, &self.title)
+            && (self.author.is_none()
+                || contains(level.submitted_by.as_deref().unwrap_or(""), &self.author))
+            && self
+                .featured
+                .is_none_or(|featured| featured == level.featured)
+            && self.colors.contains(puzzle.color_count() as u64)
+            && self.marks.contains(puzzle.mark_count() as u64)
+            && self.slots.contains(puzzle.slot_count() as u64)
+            && self.solved.is_none_or(|wanted| wanted == solved(level.id))
+    }
+    /// The matching levels in the order of the query, `solved` tells whether
+    /// a level has been solved.
+    pub fn select(
+        &self,
+        levels: impl Iterator<Item = Level>,
+        solved: impl Fn(u64) -> bool,
+    ) -> Vec<Level> {
+        let mut result: Vec<Level> = levels
+            .filter(|level| self.matches(level, &solved))
+            .collect();
+        if let Some(key) = self.sort {
+            result.sort_by(|a, b| {
+                let order = match key {
+                    SortKey::Id => a.id.cmp(&b.id),
+                    SortKey::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+                    // levels without votes go last either way
+                    SortKey::Difficulty => match (a.difficulty(), b.difficulty()) {
+                        (Some(a), Some(b)) => a.total_cmp(&b),
+                        (a, b) => return b.is_some().cmp(&a.is_some()),
+                    },
+                    SortKey::Likes => a.likes.cmp(&b.likes),
+                    SortKey::Slots => a.puzzle.slot_count().cmp(&b.puzzle.slot_count()),
+                    SortKey::Stars => a.puzzle.stars.cmp(&b.puzzle.stars),
+                };
+                let order = if self.descending {
+                    order.reverse()
+                } else {
+                    order
+                };
+                order.then(a.id.cmp(&b.id))
+            });
+        }
+        result
+    }
+}
+
+/// What `puzzles list` shows of a level.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ListedPuzzle {
+    pub id: u64,
+    pub title: String,
+    pub author: Option<String>,
+    pub featured: bool,
+    pub colors: usize,
+    pub marks: usize,
+    pub slots: usize,
+    pub stars: usize,
+    pub difficulty: Option<f64>,
+    pub likes: u64,
+    pub solved: bool,
+}
+
+impl ListedPuzzle {
+    pub fn new(level: &Level, solved: bool) -> Self {
+        ListedPuzzle {
+            id: level.id,
+            title: level.title.clone(),
+            author: level.submitted_by.clone(),
+            featured: level.featured,
+            colors: level.puzzle.color_count(),
+            marks: level.puzzle.mark_count(),
+            slots: level.puzzle.slot_count(),
+            stars: level.puzzle.stars,
+            difficulty: level.difficulty(),
+            likes: level.likes,
+            solved,
+        }
+    }
+}
+
+const TITLE_WIDTH: usize = 32;
+const AUTHOR_WIDTH: usize = 16;
+
+/// The listed puzzles as a table with a header line.
+pub fn puzzle_table(puzzles: &[ListedPuzzle]) -> String {
+    let mut result = format!(
+        "{:>5}  {:<title$}  {:<author$}  {:<8}  {:>6}  {:>5}  {:>5}  {:>5}  {:>10}  {:>5}  {}\n",
+        "Id",
+        "Title",
+        "Author",
+        "Featured",
+        "Colors",
+        "Marks",
+        "Slots",
+        "Stars",
+        "Difficulty",
+        "Likes",
+        "Solved",
+        title = TITLE_WIDTH,
+        author = AUTHOR_WIDTH,
+    );
+    for puzzle in puzzles {
+        writeln!(
+            result,
+            "{:>5}  {:<title$}  {:<author$}  {:<8}  {:>6}  {:>5}  {:>5}  {:>5}  {:>10}  {:>5}  {}",
+            puzzle.id,
+            truncate(&puzzle.title, TITLE_WIDTH),
+            truncate(puzzle.author.as_deref().unwrap_or("-"), AUTHOR_WIDTH),
+            if puzzle.featured { "yes" } else { "no" },
+            puzzle.colors,
+            puzzle.marks,
+            puzzle.slots,
+            puzzle.stars,
+            puzzle
+                .difficulty
+                .map_or("-".to_string(), |difficulty| format!("{:.2}", difficulty)),
+            puzzle.likes,
+            if puzzle.solved { "yes" } else { "no" },
+            title = TITLE_WIDTH,
+            author = AUTHOR_WIDTH,
+        )
+        .expect("writing to a string");
+    }
+    result
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        let mut result: String = text.chars().take(width - 1).collect();
+        result.push('…');
+        result
+    }
+}
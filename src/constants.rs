@@ -1,3 +1,5 @@
+use std::marker::PhantomData;
+
 use super::game::{puzzle::Puzzle, *};
 use crate::game::board::Board;
 use crate::game::instructions::*;
@@ -95,6 +97,7 @@ pub(crate) const PUZZLE_NULL: Puzzle = Puzzle {
     red: false,
     green: false,
     blue: false,
+    pointer: PhantomData,
 };
 
 const RAND_FUNCS: [Method; 9] = [
@@ -195,6 +198,7 @@ pub(crate) const PUZZLE_42: Puzzle = Puzzle {
     red: false,
     green: false,
     blue: true,
+    pointer: PhantomData,
 };
 pub(crate) const PUZZLE_42_SOLUTION: Source = Source([
     [F2, LEFT, F3, LEFT, F1, HALT, HALT, HALT, HALT, HALT],
@@ -262,6 +266,7 @@ pub(crate) const PUZZLE_536: Puzzle = Puzzle {
     red: true,
     green: true,
     blue: true,
+    pointer: PhantomData,
 };
 pub(crate) const PUZZLE_536_SOLUTION: Source = Source([
     [F2, RIGHT, F1, HALT, HALT, HALT, HALT, HALT, HALT, HALT],
@@ -329,6 +334,7 @@ pub(crate) const PUZZLE_656: Puzzle = Puzzle {
     red: true,
     green: false,
     blue: true,
+    pointer: PhantomData,
 };
 pub(crate) const PUZZLE_656_SOLUTION: Source = Source([
     [LEFT, F2, LEFT, FORWARD, F1, HALT, HALT, HALT, HALT, HALT],
@@ -397,6 +403,7 @@ pub(crate) const PUZZLE_1337: Puzzle = Puzzle {
     red: true,
     green: true,
     blue: true,
+    pointer: PhantomData,
 };
 pub(crate) const PUZZLE_1337_SOLUTION: Source = Source([
     [
@@ -485,6 +492,7 @@ pub(crate) const PUZZLE_TEST_1: Puzzle = Puzzle {
     red: true,
     green: true,
     blue: true,
+    pointer: PhantomData,
 };
 
 pub(crate) const PUZZLE_TEST_1_SOLUTION: Source = Source([
@@ -0,0 +1,177 @@
+use std::fmt::Write as _;
+use std::io::{stdout, Write};
+use std::time::Duration;
+
+use colored::Colorize;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use crossterm::{execute, queue};
+
+use crate::game::history::History;
+use crate::game::instructions::InsPointer;
+use crate::game::puzzle::Puzzle;
+use crate::game::state::Termination;
+use crate::game::Source;
+
+/// Call frames listed before the rest of the stack is summarized.
+const SHOWN_FRAMES: usize = 12;
+const SPEEDS: [Duration; 5] = [
+    Duration::from_millis(800),
+    Duration::from_millis(400),
+    Duration::from_millis(200),
+    Duration::from_millis(80),
+    Duration::from_millis(20),
+];
+const KEYS: &str = "→/l step  ←/h back  space run/pause  g/G start/end  +/- speed  q quit";
+
+/// Steps through `source` on `puzzle` in a full-screen terminal view until
+/// the user quits.
+pub fn debug(puzzle: &Puzzle, source: Source, title: &str) -> std::io::Result<()> {
+    let _terminal = Terminal::enter()?;
+    let mut history = History::new(puzzle, source);
+    let (mut running, mut speed) = (false, 2);
+    loop {
+        let mut out = stdout();
+        queue!(out, MoveTo(0, 0), Clear(ClearType::All))?;
+        let status = if running { "running" } else { "paused" };
+        let frame = format!("{}\n{} | {}\n", render(&history, title), status, KEYS);
+        write!(out, "{}", frame.replace('\n', "\r\n"))?;
+        out.flush()?;
+
+        if running && !event::poll(SPEEDS[speed])? {
+            running = history.step().is_some() && !history.finished();
+            continue;
+        }
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Char('n') => {
+                running = false;
+                history.step();
+            }
+            KeyCode::Left | KeyCode::Char('h') | KeyCode::Char('b') => {
+                running = false;
+                history.step_back();
+            }
+            KeyCode::Char(' ') => running = !running && !history.finished(),
+            KeyCode::Home | KeyCode::Char('g') => {
+                history.jump(0);
+            }
+            KeyCode::End | KeyCode::Char('G') => {
+                running = false;
+                history.jump(usize::MAX);
+            }
+            KeyCode::Char('+') => speed = (speed + 1).min(SPEEDS.len() - 1),
+            KeyCode::Char('-') => speed = speed.saturating_sub(1),
+            _ => (),
+        }
+    }
+}
+
+/// The board, the methods with the next instruction highlighted and the call
+/// stack of the current state.
+pub fn render(history: &History, title: &str) -> String {
+    let state = history.state();
+    let source = history.source();
+    let puzzle = history.puzzle();
+    let mut out = String::new();
+    let outcome = match state.termination() {
+        Some(Termination::Won) => "won".green().to_string(),
+        Some(termination) => format!("lost: {:?}", termination).red().to_string(),
+        None => "".to_string(),
+    };
+    writeln!(
+        out,
+        "{} | step {} | stars left {} | stack {} {}",
+        title.bold(),
+        history.position(),
+        state.stars,
+        state.stack.len(),
+        outcome
+    )
+    .expect("writing to a string");
+    write!(out, "{}", state.board).expect("writing to a string");
+    if let Some(event) = history.events().last() {
+        writeln!(
+            out,
+            "last: F{}[{}] {:?} {}",
+            event.method + 1,
+            event.index,
+            event.instruction,
+            if event.executed { "" } else { "(skipped)" }
+        )
+        .expect("writing to a string");
+    }
+    let next = (!history.finished()).then(|| *state.ins_pointer());
+    for (m, &length) in puzzle.methods.iter().enumerate() {
+        if length == 0 {
+            continue;
+        }
+        write!(out, "F{}:", m + 1).expect("writing to a string");
+        for i in 0..length {
+            let ins = source[m][i];
+            match next {
+                Some(pointer)
+                    if (pointer.get_method_index(), pointer.get_ins_index()) == (m, i) =>
+                {
+                    write!(out, "[{}]", ins)
+                }
+                _ => write!(out, " {} ", ins),
+            }
+            .expect("writing to a string");
+        }
+        writeln!(out).expect("writing to a string");
+    }
+    write!(out, "stack:").expect("writing to a string");
+    let frames = call_frames(history);
+    for (method, index) in frames.iter().take(SHOWN_FRAMES) {
+        write!(out, " F{}[{}]", method + 1, index).expect("writing to a string");
+    }
+    if frames.len() > SHOWN_FRAMES {
+        write!(out, " ... {} more", frames.len() - SHOWN_FRAMES).expect("writing to a string");
+    }
+    writeln!(out).expect("writing to a string");
+    out
+}
+
+/// The (method, instruction) each active call resumes at, innermost first.
+/// The stack holds every instruction still to be executed, so a call frame
+/// is a run of consecutive instructions of the same method.
+fn call_frames(history: &History) -> Vec<(usize, usize)> {
+    let stack = &history.state().stack.0;
+    let mut frames: Vec<(usize, usize)> = vec![];
+    let mut previous = None;
+    for pointer in stack.iter().rev() {
+        let (method, index) = (pointer.get_method_index(), pointer.get_ins_index());
+        let continues = matches!(previous, Some((m, i)) if m == method && i + 1 == index);
+        if !continues {
+            frames.push((method, index));
+        }
+        previous = Some((method, index));
+    }
+    frames
+}
+
+/// Raw mode on the alternate screen, restored when dropped.
+struct Terminal;
+
+impl Terminal {
+    fn enter() -> std::io::Result<Terminal> {
+        enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen, Hide)?;
+        Ok(Terminal)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
@@ -2,10 +2,10 @@ use std::collections::hash_map::DefaultHasher;
 use std::fmt::{Debug, Display};
 use std::hash::{Hash, Hasher};
 use std::mem;
-use std::ops::{Index, IndexMut};
+use std::ops::{Index, IndexMut, Range};
 
 use colored::*;
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use smallvec::SmallVec;
 
 use crate::constants::*;
@@ -16,9 +16,13 @@ use self::puzzle::{make_puzzle, Puzzle};
 
 pub mod board;
 pub mod display;
+pub mod history;
 pub mod instructions;
+pub mod notation;
 pub mod puzzle;
 pub mod state;
+pub mod trace;
+pub mod verify;
 
 #[cfg(test)]
 mod tests;
@@ -48,6 +52,10 @@ impl Tile {
     fn color(&self) -> Tile {
         Tile(self.0 & TILE_COLOR_MASK.0)
     }
+    /// The color and star of the tile, without the touch count.
+    pub(crate) fn layout(&self) -> Tile {
+        Tile(self.0 & (TILE_COLOR_MASK.0 | TILE_STAR_MASK.0))
+    }
     fn is_red(&self) -> bool {
         self.0 & RE.0 > 0
     }
@@ -70,24 +78,98 @@ impl Tile {
     }
 }
 
-#[derive(PartialEq, Eq, Copy, Clone, Hash, Serialize, Deserialize)]
-pub struct Map(pub [[Tile; 18]; 14]);
+/// Width and height of the boards of the puzzles on robozzle.
+pub const STANDARD_WIDTH: usize = 16;
+pub const STANDARD_HEIGHT: usize = 12;
+/// Width and height of the [`Map`] of a standard board, with its border.
+pub const MAP_WIDTH: usize = STANDARD_WIDTH + 2;
+pub const MAP_HEIGHT: usize = STANDARD_HEIGHT + 2;
+
+/// The tiles of a board surrounded by a border of `_N` tiles, so that a
+/// robot walking off the board always lands on an empty tile. `W` and `H`
+/// include the border, the defaults are the size of the standard boards.
+#[derive(PartialEq, Eq, Copy, Clone, Hash)]
+pub struct Map<const W: usize = MAP_WIDTH, const H: usize = MAP_HEIGHT>(pub [[Tile; W]; H]);
+
+impl<const W: usize, const H: usize> Map<W, H> {
+    /// Columns and rows inside the border.
+    pub const WIDTH: usize = W - 2;
+    pub const HEIGHT: usize = H - 2;
 
-impl Map {
+    pub fn empty() -> Self {
+        Map([[_N; W]; H])
+    }
+    /// The (x, y) coordinates of the tiles inside the border, row by row.
+    pub fn coordinates() -> impl Iterator<Item = (usize, usize)> {
+        (1..H - 1).flat_map(|y| (1..W - 1).map(move |x| (x, y)))
+    }
     pub fn count_stars(&self) -> usize {
         self.0
             .iter()
             .map(|row| row.iter().map(|el| el.has_star() as usize).sum::<usize>())
             .sum()
     }
+    /// The smallest rectangle holding every tile that isn't empty, as ranges
+    /// of rows and columns.
+    pub(crate) fn bounds(&self) -> (Range<usize>, Range<usize>) {
+        let (mut miny, mut minx, mut maxy, mut maxx) = (H, W, 0, 0);
+        for (x, y) in Self::coordinates() {
+            if self.0[y][x] != _N {
+                miny = miny.min(y);
+                minx = minx.min(x);
+                maxy = maxy.max(y + 1);
+                maxx = maxx.max(x + 1);
+            }
+        }
+        (miny..maxy, minx..maxx)
+    }
+}
+
+// serde only implements its traits for arrays of up to 32 elements, so
+// maps are (de)serialized as nested sequences
+impl<const W: usize, const H: usize> Serialize for Map<W, H> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|row| row.as_slice()))
+    }
+}
+
+impl<'de, const W: usize, const H: usize> Deserialize<'de> for Map<W, H> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rows = Vec::<Vec<Tile>>::deserialize(deserializer)?;
+        if rows.len() != H {
+            return Err(de::Error::invalid_length(rows.len(), &"a map row per row"));
+        }
+        let mut map = Map::empty();
+        for (y, row) in rows.iter().enumerate() {
+            if row.len() != W {
+                return Err(de::Error::invalid_length(row.len(), &"a tile per column"));
+            }
+            map.0[y].copy_from_slice(row);
+        }
+        Ok(map)
+    }
 }
 
-pub type Method = [Ins; 10];
+/// Methods of a robozzle program.
+pub const STANDARD_METHODS: usize = 5;
+/// Instructions a robozzle method may have.
+pub const STANDARD_METHOD_LENGTH: usize = 10;
+
+pub type Method<const M: usize = STANDARD_METHOD_LENGTH> = [Ins; M];
 
-#[derive(Eq, Ord, PartialEq, PartialOrd, Copy, Clone, Serialize, Deserialize)]
-pub struct Source(pub [Method; 5]);
+/// A program of `N` methods with room for `M` instructions each, robozzle
+/// programs are the default 5 by 10. Both are bounded by [`MAX_METHODS`] and
+/// [`MAX_METHOD_LENGTH`].
+#[derive(Eq, Ord, PartialEq, PartialOrd, Copy, Clone)]
+pub struct Source<const N: usize = STANDARD_METHODS, const M: usize = STANDARD_METHOD_LENGTH>(
+    pub [Method<M>; N],
+);
 
-impl Source {
+impl<const N: usize, const M: usize> Source<N, M> {
+    /// A program without any instructions.
+    pub const fn halted() -> Self {
+        Source([[HALT; M]; N])
+    }
     fn len(&self) -> usize {
         self.0.len()
     }
@@ -125,9 +207,9 @@ impl Source {
     }
     pub fn shade(&mut self, max_ins: usize) {
         let diff = max_ins - self.count_ins();
-        for m in 0..5 {
+        for m in 0..N {
             let mut nops = 0;
-            for i in 0..10 {
+            for i in 0..M {
                 nops += self[m][i].is_nop() as usize;
 
                 if nops > diff {
@@ -137,13 +219,13 @@ impl Source {
         }
     }
     pub fn sanitize(&mut self) {
-        for m in 0..5 {
+        for m in 0..N {
             let mut offset = 0;
-            for i in 0..10 {
+            for i in 0..M {
                 if self[m][i].is_nop() || self[m][i].is_probe() {
                     offset += 1;
                 }
-                self[m][i] = if i + offset < 10 {
+                self[m][i] = if i + offset < M {
                     self[m][i + offset]
                 } else {
                     HALT
@@ -153,8 +235,8 @@ impl Source {
     }
 }
 
-impl Hash for Source {
-    fn hash<H: Hasher>(&self, state: &mut H) {
+impl<const N: usize, const M: usize> Hash for Source<N, M> {
+    fn hash<T: Hasher>(&self, state: &mut T) {
         for method in self.0.iter() {
             for ins in method {
                 ins.hash(state);
@@ -163,79 +245,103 @@ impl Hash for Source {
     }
 }
 
-impl Index<usize> for Source {
-    type Output = Method;
+impl<const N: usize, const M: usize> Index<usize> for Source<N, M> {
+    type Output = Method<M>;
 
     fn index(&self, index: usize) -> &Self::Output {
         &self.0[index]
     }
 }
 
-impl IndexMut<usize> for Source {
+impl<const N: usize, const M: usize> IndexMut<usize> for Source<N, M> {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
         &mut self.0[index]
     }
 }
 
-type Stack = StackVec;
+impl<const N: usize, const M: usize> Serialize for Source<N, M> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|method| method.as_slice()))
+    }
+}
+
+impl<'de, const N: usize, const M: usize> Deserialize<'de> for Source<N, M> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let methods = Vec::<Vec<Ins>>::deserialize(deserializer)?;
+        if methods.len() != N {
+            return Err(de::Error::invalid_length(
+                methods.len(),
+                &"a list per method",
+            ));
+        }
+        let mut source = Source::halted();
+        for (m, method) in methods.iter().enumerate() {
+            if method.len() != M {
+                return Err(de::Error::invalid_length(
+                    method.len(),
+                    &"a slot per instruction",
+                ));
+            }
+            source.0[m].copy_from_slice(method);
+        }
+        Ok(source)
+    }
+}
+
+type Stack<P = InsPtr> = StackVec<P>;
 
 pub(crate) const MAX_STEPS: usize = 1 << 12;
 const STACK_MATCH: usize = 1 << 6;
 
+/// The pointers of the instructions left to execute, the next one last. `P`
+/// is picked by the [`Puzzle`](puzzle::Puzzle) for the size of its programs.
 #[derive(Clone, Debug)]
-pub struct StackVec(pub SmallVec<[InsPtr; 1 << 8]>);
+pub struct StackVec<P = InsPtr>(pub SmallVec<[P; 1 << 8]>);
 
-impl Default for StackVec {
+impl<P> Default for StackVec<P> {
     fn default() -> Self {
         Self(Default::default())
     }
 }
-impl PartialEq for StackVec {
+impl<P: InsPointer> PartialEq for StackVec<P> {
     fn eq(&self, other: &Self) -> bool {
-        if self.0.len() != other.0.len() {
-            return false;
-        } else if self.0.len() <= STACK_MATCH {
-            return self.0 == other.0;
-        } else if self.0.len() > STACK_MATCH {
-            return self.0.get((self.0.len() - STACK_MATCH)..self.0.len())
-                == other.0.get((self.0.len() - STACK_MATCH)..self.0.len());
-        } else {
-            return false;
-        }
+        self.len() == other.len() && self.top(STACK_MATCH) == other.top(STACK_MATCH)
     }
 }
 
-impl Eq for StackVec {}
+impl<P: InsPointer> Eq for StackVec<P> {}
 
-impl Hash for StackVec {
+impl<P: InsPointer> Hash for StackVec<P> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.0
-            .get((self.0.len() - STACK_MATCH).max(0)..self.0.len())
-            .hash(state);
+        self.top(STACK_MATCH).hash(state);
     }
 }
-impl Index<usize> for StackVec {
-    type Output = InsPtr;
+impl<P> Index<usize> for StackVec<P> {
+    type Output = P;
     fn index(&self, index: usize) -> &Self::Output {
         &self.0[self.0.len() - index - 1]
     }
 }
-impl StackVec {
-    fn push(&mut self, element: InsPtr) {
+impl<P: InsPointer> StackVec<P> {
+    fn push(&mut self, element: P) {
         self.0.push(element);
     }
-    fn pop(&mut self) -> InsPtr {
+    fn pop(&mut self) -> P {
         self.0
             .pop()
             .expect("pop() shouldn't be called on an empty stack")
     }
-    pub fn last(&self) -> &InsPtr {
-        self.0.last().unwrap_or(&INSPTR_NULL)
+    pub fn last(&self) -> &P {
+        self.0.last().unwrap_or(P::NULL)
         //.expect("last() shouldn't be called on an empty stack")
     }
     pub(crate) fn len(&self) -> usize {
         self.0.len()
     }
+    /// The `count` entries popped next, or the whole stack if it is shorter.
+    pub(crate) fn top(&self, count: usize) -> &[P] {
+        &self.0[self.len().saturating_sub(count)..]
+    }
     fn is_empty(&self) -> bool {
         self.len() == 0
     }
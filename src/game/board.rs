@@ -8,17 +8,18 @@ use serde::{Deserialize, Serialize};
 
 use crate::constants::*;
 
-use super::{instructions::Ins, Direction, Map, Tile};
+use super::{instructions::Ins, Direction, Map, Tile, MAP_HEIGHT, MAP_WIDTH};
 
+/// A [`Map`] with the robot on it, see [`Map`] for `W` and `H`.
 #[derive(Eq, PartialEq, Copy, Clone, Hash, Serialize, Deserialize)]
-pub struct Board {
-    pub map: Map,
+pub struct Board<const W: usize = MAP_WIDTH, const H: usize = MAP_HEIGHT> {
+    pub map: Map<W, H>,
     pub direction: Direction,
     pub x: usize,
     pub y: usize,
 }
 
-impl Board {
+impl<const W: usize, const H: usize> Board<W, H> {
     pub(crate) fn clear_star(&mut self) {
         self.map.0[self.y][self.x].clear_star();
     }
@@ -69,10 +70,10 @@ impl Board {
     }
 }
 
-impl Default for Board {
+impl<const W: usize, const H: usize> Default for Board<W, H> {
     fn default() -> Self {
         Self {
-            map: Map([[_N; 18]; 14]),
+            map: Map::empty(),
             direction: Direction::Up,
             x: 1,
             y: 1,
@@ -81,22 +82,12 @@ impl Default for Board {
 }
 
 #[allow(non_snake_case)]
-impl Display for Board {
+impl<const W: usize, const H: usize> Display for Board<W, H> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Map:")?;
-        let (mut miny, mut minx, mut maxy, mut maxx) = (14, 18, 0, 0);
-        for y in 1..13 {
-            for x in 1..17 {
-                if self.map.0[y][x] != _N {
-                    miny = miny.min(y);
-                    minx = minx.min(x);
-                    maxy = maxy.max(y + 1);
-                    maxx = maxx.max(x + 1);
-                }
-            }
-        }
-        for y in miny..maxy {
-            for x in minx..maxx {
+        let (rows, columns) = self.map.bounds();
+        for y in rows {
+            for x in columns.clone() {
                 let tile = self.map.0[y][x];
                 let string = if self.y == y && self.x == x {
                     match self.direction {
@@ -129,7 +120,7 @@ impl Display for Board {
     }
 }
 
-impl Debug for Board {
+impl<const W: usize, const H: usize> Debug for Board<W, H> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // write!(
         //     f,
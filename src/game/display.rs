@@ -10,7 +10,7 @@ use super::board::Board;
 use super::state::State;
 use super::*;
 
-impl Display for Source {
+impl<const N: usize, const M: usize> Display for Source<N, M> {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         write!(f, "{{")?;
         for i in 0..self.len() {
@@ -33,7 +33,7 @@ impl Display for Source {
         write!(f, "}}")
     }
 }
-impl Debug for Source {
+impl<const N: usize, const M: usize> Debug for Source<N, M> {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         write!(f, "{{")?;
         for i in 0..self.len() {
@@ -57,28 +57,18 @@ impl Debug for Source {
     }
 }
 
-impl Debug for Map {
+impl<const W: usize, const H: usize> Debug for Map<W, H> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         write!(f, "{}", self)
     }
 }
-impl Display for Map {
+impl<const W: usize, const H: usize> Display for Map<W, H> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         write!(f, "{{")?;
         writeln!(f, "\nmap:")?;
-        let (mut miny, mut minx, mut maxy, mut maxx) = (14, 18, 0, 0);
-        for y in 1..13 {
-            for x in 1..17 {
-                if self.0[y][x] != _N {
-                    miny = miny.min(y);
-                    minx = minx.min(x);
-                    maxy = maxy.max(y + 1);
-                    maxx = maxx.max(x + 1);
-                }
-            }
-        }
-        for y in miny..maxy {
-            for x in minx..maxx {
+        let (rows, columns) = self.bounds();
+        for y in rows {
+            for x in columns.clone() {
                 let tile = self.0[y][x];
                 let string = "★";
                 let background = match tile.color() {
@@ -0,0 +1,128 @@
+use super::instructions::{InsPointer, InsPtr};
+use super::puzzle::Puzzle;
+use super::state::State;
+use super::trace::{StepEvent, TileColor};
+use super::Source;
+use crate::constants::*;
+
+/// Where [`History::run`] pauses.
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug)]
+pub enum Breakpoint {
+    /// The robot moves onto the tile at board coordinates (x, y).
+    Tile { x: usize, y: usize },
+    /// The robot picks up a star.
+    Star,
+    /// The instruction at the pointer is up next.
+    Pointer(InsPtr),
+}
+
+impl Breakpoint {
+    fn hit(&self, event: &StepEvent, state: &State) -> bool {
+        match *self {
+            Breakpoint::Tile { x, y } => {
+                (event.after.x, event.after.y) == (x, y)
+                    && (event.before.x, event.before.y) != (x, y)
+            }
+            Breakpoint::Star => event.star_collected,
+            Breakpoint::Pointer(pointer) => state.running() && *state.ins_pointer() == pointer,
+        }
+    }
+}
+
+/// A [`State`] that remembers its steps, so that they can be undone.
+pub struct History<'a> {
+    puzzle: &'a Puzzle,
+    source: Source,
+    state: State,
+    events: Vec<StepEvent>,
+    pub breakpoints: Vec<Breakpoint>,
+}
+
+impl<'a> History<'a> {
+    pub fn new(puzzle: &'a Puzzle, source: Source) -> Self {
+        History {
+            state: puzzle.initial_state(&source),
+            puzzle,
+            source,
+            events: vec![],
+            breakpoints: vec![],
+        }
+    }
+    pub fn puzzle(&self) -> &Puzzle {
+        self.puzzle
+    }
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+    /// The events of the steps taken so far, the last one is undone first.
+    pub fn events(&self) -> &[StepEvent] {
+        &self.events
+    }
+    /// The number of steps taken so far.
+    pub fn position(&self) -> usize {
+        self.events.len()
+    }
+    pub fn finished(&self) -> bool {
+        !self.state.running()
+    }
+    /// Takes a step, unless the program is finished.
+    pub fn step(&mut self) -> Option<&StepEvent> {
+        if self.finished() {
+            return None;
+        }
+        let event = self.state.traced_step(&self.source, self.puzzle);
+        self.events.push(event);
+        self.events.last()
+    }
+    /// Undoes the last step, unless at the start.
+    pub fn step_back(&mut self) -> Option<StepEvent> {
+        let event = self.events.pop()?;
+        let state = &mut self.state;
+        if event.touched {
+            state.board.untouch();
+        }
+        if event.star_collected {
+            state.board.current_tile_mut().0 |= TILE_STAR_MASK.0;
+            state.stars += 1;
+        }
+        if let Some((color, _)) = event.repainted {
+            let tile = state.board.current_tile_mut();
+            tile.0 = (tile.0 & !TILE_COLOR_MASK.0) | TileColor::to_tile(color).0;
+        }
+        state.board.x = event.before.x;
+        state.board.y = event.before.y;
+        state.board.direction = event.before.direction;
+        state.stack.0.truncate(event.stack_before - 1);
+        state.stack.push(InsPtr::new(event.method, event.index));
+        state.steps -= 1;
+        state.termination = None;
+        Some(event)
+    }
+    /// Steps forward or back until `step` steps are taken or the program
+    /// finishes, returning the number of steps taken.
+    pub fn jump(&mut self, step: usize) -> usize {
+        while self.position() > step {
+            self.step_back();
+        }
+        while self.position() < step && self.step().is_some() {}
+        self.position()
+    }
+    /// Steps until a breakpoint is hit, the program finishes or `max_steps`
+    /// steps are taken, returning the breakpoint that was hit.
+    pub fn run(&mut self, max_steps: usize) -> Option<Breakpoint> {
+        for _ in 0..max_steps {
+            let event = self.step()?.clone();
+            let hit = self
+                .breakpoints
+                .iter()
+                .find(|breakpoint| breakpoint.hit(&event, &self.state));
+            if hit.is_some() {
+                return hit.copied();
+            }
+        }
+        None
+    }
+}
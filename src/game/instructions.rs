@@ -1,4 +1,5 @@
 use std::fmt::{Debug, Display, Error, Formatter};
+use std::hash::Hash;
 
 use colored::*;
 use serde::{Deserialize, Serialize};
@@ -10,13 +11,16 @@ pub struct Ins(pub InsType); // a single instruction
 
 impl Ins {
     pub fn condition_to_color(self) -> Ins {
-        Ins(self.as_vanilla().0 >> 5)
+        Ins(self.get_cond().0 >> 5)
     }
+    /// The method called by a function instruction. Methods past F5 reuse the
+    /// opcodes of F1 to F5 with a bank number in the high bits.
     pub fn source_index(self) -> usize {
-        (self.get_ins().0 - F1.0) as usize
+        (self.get_opcode().0 - F1.0) as usize + (self.0 >> BANK_SHIFT) as usize * FUNCTIONS.len()
     }
     pub fn fun_from_index(index: usize) -> Ins {
-        Ins(index as InsType + F1.0)
+        let (bank, offset) = (index / FUNCTIONS.len(), index % FUNCTIONS.len());
+        Ins((F1.0 + offset as InsType) | ((bank as InsType) << BANK_SHIFT))
     }
     pub fn get_cond(self) -> Ins {
         self & INS_COLOR_MASK
@@ -24,8 +28,13 @@ impl Ins {
     pub fn get_ins(self) -> Ins {
         self & INS_MASK
     }
-    fn get_fun_number(self) -> u8 {
-        (self.get_ins().0 - F1.0 + 1) as u8
+    /// The instruction without its condition or function bank, calls to any
+    /// method share the opcodes of F1 to F5.
+    pub fn get_opcode(self) -> Ins {
+        self & OPCODE_MASK
+    }
+    fn get_fun_number(self) -> usize {
+        self.source_index() + 1
     }
     pub fn get_mark_color(self) -> Ins {
         self & MARK_MASK
@@ -55,7 +64,7 @@ impl Ins {
         (self & MARK_GRAY) == MARK_GRAY
     }
     pub fn is_function(self) -> bool {
-        self.get_ins() >= F1 && self.get_ins() <= F5
+        self.get_opcode() >= F1 && self.get_opcode() <= F5
     }
     pub fn is_ins(self, ins: Ins) -> bool {
         self.get_ins() == ins
@@ -120,7 +129,15 @@ pub fn with_conds(red: bool, green: bool, blue: bool) -> Ins {
 }
 
 const LOOSE_MASK: Ins = Ins(0b0000000100000000);
-const VANILLA_MASK: Ins = Ins(0b0000000011111111);
+const VANILLA_MASK: Ins = Ins(0b1111111011111111);
+const BANK_SHIFT: u32 = 9;
+
+/// Most methods a [`Source`](super::Source) may have, bounded by the bits of
+/// a [`WideInsPtr`].
+pub const MAX_METHODS: usize = 0xFF;
+/// Most instructions a method may have, bounded by the bits of a
+/// [`WideInsPtr`].
+pub const MAX_METHOD_LENGTH: usize = 0xFF;
 
 impl From<Ins> for u8 {
     fn from(ins: Ins) -> Self {
@@ -162,17 +179,37 @@ impl std::ops::Not for Ins {
     }
 }
 
+/// A pointer to an instruction of a program, the entries of the stack of a
+/// [`State`](super::state::State). The method and the slot share the bits of
+/// the pointer, the highest of both is left to the null pointer.
+pub trait InsPointer: Copy + Eq + Ord + Hash + Debug + 'static {
+    /// Most methods a program may have for the pointer to address it.
+    const MAX_METHODS: usize;
+    /// Most instructions a method may have for the pointer to address it.
+    const MAX_METHOD_LENGTH: usize;
+    /// The pointer of an empty stack.
+    const NULL: &'static Self;
+    fn new(method: usize, ins: usize) -> Self;
+    fn get_ins_index(self) -> usize;
+    fn get_method_index(self) -> usize;
+}
+
+/// The pointer of programs up to 15 methods of 15 instructions, which keeps
+/// the stack of a standard program a byte per entry.
 #[derive(PartialEq, Eq, Ord, PartialOrd, Copy, Clone, Hash, Serialize, Deserialize, Debug)]
 pub struct InsPtr(u8);
 
-impl InsPtr {
-    pub fn new(method: usize, ins: usize) -> InsPtr {
+impl InsPointer for InsPtr {
+    const MAX_METHODS: usize = 0xF;
+    const MAX_METHOD_LENGTH: usize = 0xF;
+    const NULL: &'static Self = &INSPTR_NULL;
+    fn new(method: usize, ins: usize) -> InsPtr {
         InsPtr(((ins as u8) & INSPTR_INS_MASK) | ((method as u8) << 4))
     }
-    pub fn get_ins_index(self) -> usize {
+    fn get_ins_index(self) -> usize {
         (self.0 & INSPTR_INS_MASK) as usize
     }
-    pub fn get_method_index(self) -> usize {
+    fn get_method_index(self) -> usize {
         ((self.0 & INSPTR_METHOD_MASK) >> 4) as usize
     }
 }
@@ -181,6 +218,30 @@ pub const INSPTR_NULL: InsPtr = InsPtr(0b11111111);
 const INSPTR_INS_MASK: u8 = 0b00001111;
 const INSPTR_METHOD_MASK: u8 = 0b11110000;
 
+/// The pointer of programs up to [`MAX_METHODS`] methods of
+/// [`MAX_METHOD_LENGTH`] instructions, a byte each for the method and the
+/// slot.
+#[derive(PartialEq, Eq, Ord, PartialOrd, Copy, Clone, Hash, Serialize, Deserialize, Debug)]
+pub struct WideInsPtr(u16);
+
+impl InsPointer for WideInsPtr {
+    const MAX_METHODS: usize = MAX_METHODS;
+    const MAX_METHOD_LENGTH: usize = MAX_METHOD_LENGTH;
+    const NULL: &'static Self = &WideInsPtr(0xFFFF);
+    fn new(method: usize, ins: usize) -> WideInsPtr {
+        WideInsPtr(((ins as u16) & WIDE_INSPTR_INS_MASK) | ((method as u16) << 8))
+    }
+    fn get_ins_index(self) -> usize {
+        (self.0 & WIDE_INSPTR_INS_MASK) as usize
+    }
+    fn get_method_index(self) -> usize {
+        ((self.0 & WIDE_INSPTR_METHOD_MASK) >> 8) as usize
+    }
+}
+
+const WIDE_INSPTR_INS_MASK: u16 = 0x00FF;
+const WIDE_INSPTR_METHOD_MASK: u16 = 0xFF00;
+
 impl Display for Ins {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         let background = match self.get_cond() {
@@ -204,7 +265,7 @@ impl Display for Ins {
             FORWARD => "↑".to_string(),
             LEFT => "←".to_string(),
             RIGHT => "→".to_string(),
-            F1 | F2 | F3 | F4 | F5 => ins.get_fun_number().to_string(),
+            _ if ins.is_function() => ins.get_fun_number().to_string(),
             MARK_RED | MARK_GREEN | MARK_BLUE => "●".to_string(),
             NOP => if self.is_probe() { "_" } else { " " }.to_string(),
             _ => " ".to_string(),
@@ -235,6 +296,9 @@ impl Debug for Ins {
                     _ => "",
                 }
             )?;
+            if ins.is_function() {
+                return write!(f, "F{}", ins.get_fun_number());
+            }
             write!(
                 f,
                 "{}",
@@ -242,11 +306,6 @@ impl Debug for Ins {
                     FORWARD => "FORWARD",
                     LEFT => "LEFT",
                     RIGHT => "RIGHT",
-                    F1 => "F1",
-                    F2 => "F2",
-                    F3 => "F3",
-                    F4 => "F4",
-                    F5 => "F5",
                     MARK_RED => "MARK_RED",
                     MARK_GREEN => "MARK_GREEN",
                     MARK_BLUE => "MARK_BLUE",
@@ -285,7 +344,8 @@ pub(crate) const CYAN_COND: Ins = Ins(0b11000000);
 
 // masks for isolating instruction parts
 pub(crate) const MARK_MASK: Ins = Ins(0b00000111);
-pub(crate) const INS_MASK: Ins = Ins(0b00011111);
+pub(crate) const INS_MASK: Ins = Ins(0b1111111000011111);
+pub(crate) const OPCODE_MASK: Ins = Ins(0b00011111);
 pub(crate) const INS_COLOR_MASK: Ins = Ins(0b11100000);
 
 // iterable lists of constants
@@ -0,0 +1,298 @@
+use std::fmt::{Display, Error, Formatter};
+use std::str::FromStr;
+
+use super::instructions::*;
+use super::puzzle::Puzzle;
+use super::Source;
+
+// Plain-ASCII program notation, e.g. `F1: R> f B1 | F2: f f <`.
+//
+// Methods are separated by `|` and may be labelled with `F<n>:`, unlabelled
+// methods take the next method number. Each instruction is an optional
+// condition (`R`, `G` or `B`) followed by a command:
+//   f       forward
+//   <  >    turn left, turn right
+//   1 - 5   call F1 - F5, larger numbers call the methods of bigger programs
+//   r g b   paint red, green, blue
+//   _       unallocated slot (a probe when conditioned)
+//   .       empty slot in the middle of a method
+// Trailing empty slots are left out. Methods are numbered the way the solver
+// numbers them, i.e. after `make_puzzle` has sorted F2-F5 by length.
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ProgramError {
+    UnknownMethod(String),
+    DuplicateMethod(usize),
+    UnknownInstruction {
+        method: usize,
+        token: String,
+    },
+    MethodTooLong {
+        method: usize,
+        length: usize,
+        max: usize,
+    },
+    UnavailableMethod {
+        method: usize,
+    },
+    InvalidCharacter(char),
+    Truncated,
+    UnsupportedVersion(usize),
+    MethodLengthMismatch {
+        method: usize,
+        length: usize,
+        expected: usize,
+    },
+    Malformed(String),
+    /// The program does not fit the codes the robozzle site reads.
+    Unencodable(String),
+}
+
+impl Display for ProgramError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            ProgramError::UnknownMethod(label) => {
+                write!(
+                    f,
+                    "unknown method label '{}', expected F and a method number",
+                    label
+                )
+            }
+            ProgramError::DuplicateMethod(method) => {
+                write!(f, "F{} is defined more than once", method + 1)
+            }
+            ProgramError::UnknownInstruction { method, token } => {
+                write!(f, "unknown instruction '{}' in F{}", token, method + 1)
+            }
+            ProgramError::MethodTooLong {
+                method,
+                length,
+                max,
+            } => write!(
+                f,
+                "F{} has {} instructions but the puzzle allows {}",
+                method + 1,
+                length,
+                max
+            ),
+            ProgramError::UnavailableMethod { method } => {
+                write!(f, "F{} is not available in this puzzle", method + 1)
+            }
+            ProgramError::InvalidCharacter(c) => {
+                write!(f, "invalid character '{}' in program code", c)
+            }
+            ProgramError::Truncated => write!(f, "program code ended unexpectedly"),
+            ProgramError::UnsupportedVersion(version) => {
+                write!(f, "unsupported program code version {}", version)
+            }
+            ProgramError::MethodLengthMismatch {
+                method,
+                length,
+                expected,
+            } => write!(
+                f,
+                "F{} has {} slots in the program code but {} in the puzzle",
+                method + 1,
+                length,
+                expected
+            ),
+            ProgramError::Malformed(reason) => write!(f, "malformed program code: {}", reason),
+            ProgramError::Unencodable(reason) => {
+                write!(f, "no robozzle program code for the program: {}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProgramError {}
+
+/// Display adapter for the plain-ASCII notation, see [`Source::notation`].
+pub struct Notation<'a, const N: usize, const M: usize>(&'a Source<N, M>);
+
+impl<const N: usize, const M: usize> Source<N, M> {
+    /// Formats the program in the plain notation understood by `FromStr`.
+    /// Search annotations such as loosened conditions are not represented.
+    pub fn notation(&self) -> Notation<'_, N, M> {
+        Notation(self)
+    }
+}
+
+impl<const N: usize, const M: usize> Display for Notation<'_, N, M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let mut separate = false;
+        for (m, method) in self.0 .0.iter().enumerate() {
+            let length = method
+                .iter()
+                .rposition(|ins| !ins.is_halt())
+                .map_or(0, |i| i + 1);
+            if length == 0 {
+                continue;
+            }
+            if separate {
+                write!(f, " | ")?;
+            }
+            write!(f, "F{}:", m + 1)?;
+            for &ins in &method[..length] {
+                write!(f, " {}", instruction_notation(ins))?;
+            }
+            separate = true;
+        }
+        Ok(())
+    }
+}
+
+pub fn instruction_notation(ins: Ins) -> String {
+    let ins = ins.as_vanilla();
+    if ins.is_halt() {
+        return ".".to_string();
+    }
+    let mut result = String::new();
+    match ins.get_cond() {
+        RED_COND => result.push('R'),
+        GREEN_COND => result.push('G'),
+        BLUE_COND => result.push('B'),
+        _ => (),
+    }
+    if ins.is_function() {
+        result.push_str(&(ins.source_index() + 1).to_string());
+        return result;
+    }
+    result.push(match ins.get_ins() {
+        FORWARD => 'f',
+        LEFT => '<',
+        RIGHT => '>',
+        MARK_RED => 'r',
+        MARK_GREEN => 'g',
+        MARK_BLUE => 'b',
+        _ => '_',
+    });
+    result
+}
+
+fn parse_instruction(token: &str, method: usize) -> Result<Ins, ProgramError> {
+    let unknown = || ProgramError::UnknownInstruction {
+        method,
+        token: token.to_string(),
+    };
+    let (cond, command) = match token.chars().next() {
+        Some('R') if token.len() > 1 => (RED_COND, &token[1..]),
+        Some('G') if token.len() > 1 => (GREEN_COND, &token[1..]),
+        Some('B') if token.len() > 1 => (BLUE_COND, &token[1..]),
+        _ => (GRAY_COND, token),
+    };
+    if command.bytes().all(|digit| digit.is_ascii_digit()) {
+        return match command.parse::<usize>() {
+            Ok(number @ 1..=MAX_METHODS) => Ok(Ins::fun_from_index(number - 1) | cond),
+            _ => Err(unknown()),
+        };
+    }
+    let mut chars = command.chars();
+    let cmd = match (chars.next(), chars.next()) {
+        (Some(cmd), None) => cmd,
+        _ => return Err(unknown()),
+    };
+    let ins = match cmd {
+        'f' => FORWARD,
+        '<' => LEFT,
+        '>' => RIGHT,
+        'r' => MARK_RED,
+        'g' => MARK_GREEN,
+        'b' => MARK_BLUE,
+        '_' => NOP,
+        '.' if cond == GRAY_COND => return Ok(HALT),
+        _ => return Err(unknown()),
+    };
+    Ok(ins | cond)
+}
+
+fn parse_method_label(label: &str, methods: usize) -> Result<usize, ProgramError> {
+    let label = label.trim();
+    label
+        .strip_prefix('F')
+        .and_then(|number| number.parse::<usize>().ok())
+        .filter(|number| (1..=methods).contains(number))
+        .map(|number| number - 1)
+        .ok_or_else(|| ProgramError::UnknownMethod(label.to_string()))
+}
+
+impl<const N: usize, const M: usize> FromStr for Source<N, M> {
+    type Err = ProgramError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut result = Source::halted();
+        if text.trim().is_empty() {
+            return Ok(result);
+        }
+        let mut defined = [false; N];
+        let mut next_method = 0;
+        for segment in text.split('|') {
+            let (method, body) = match segment.split_once(':') {
+                Some((label, body)) => (parse_method_label(label, N)?, body),
+                None if next_method < N => (next_method, segment),
+                None => return Err(ProgramError::UnknownMethod(segment.trim().to_string())),
+            };
+            if defined[method] {
+                return Err(ProgramError::DuplicateMethod(method));
+            }
+            defined[method] = true;
+            next_method = method + 1;
+            let tokens: Vec<&str> = body.split_whitespace().collect();
+            if tokens.len() > result[method].len() {
+                return Err(ProgramError::MethodTooLong {
+                    method,
+                    length: tokens.len(),
+                    max: result[method].len(),
+                });
+            }
+            for (i, token) in tokens.into_iter().enumerate() {
+                result[method][i] = parse_instruction(token, method)?;
+                if result[method][i].is_function() && result[method][i].source_index() >= N {
+                    return Err(ProgramError::UnavailableMethod {
+                        method: result[method][i].source_index(),
+                    });
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Parses a program in the plain notation and checks it against the method
+/// lengths of `puzzle`.
+pub fn parse_program<
+    const W: usize,
+    const H: usize,
+    const N: usize,
+    const M: usize,
+    P: InsPointer,
+>(
+    text: &str,
+    puzzle: &Puzzle<W, H, N, M, P>,
+) -> Result<Source<N, M>, ProgramError> {
+    let source: Source<N, M> = text.parse()?;
+    for m in 0..source.len() {
+        let length = source[m]
+            .iter()
+            .rposition(|ins| !ins.is_halt())
+            .map_or(0, |i| i + 1);
+        if length > puzzle.methods[m] {
+            return Err(if puzzle.methods[m] == 0 {
+                ProgramError::UnavailableMethod { method: m }
+            } else {
+                ProgramError::MethodTooLong {
+                    method: m,
+                    length,
+                    max: puzzle.methods[m],
+                }
+            });
+        }
+        for ins in source[m].iter() {
+            if ins.is_function() && puzzle.methods[ins.source_index()] == 0 {
+                return Err(ProgramError::UnavailableMethod {
+                    method: ins.source_index(),
+                });
+            }
+        }
+    }
+    Ok(source)
+}
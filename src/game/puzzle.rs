@@ -1,8 +1,8 @@
 use std::collections::{HashSet, VecDeque};
 use std::fmt::{Debug, Display, Error, Formatter};
+use std::marker::PhantomData;
 
-use serde::Deserialize;
-use serde::Serialize;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use super::board::Board;
 use super::instructions::Ins;
@@ -10,22 +10,42 @@ use super::state::State;
 use super::Direction;
 use super::Map;
 use super::Source;
+use super::{MAP_HEIGHT, MAP_WIDTH, STANDARD_METHODS, STANDARD_METHOD_LENGTH};
 use crate::constants::*;
 use crate::game::{instructions::*, Tile};
 
+/// A board with the methods, marks and colors a program for it may use, see
+/// [`Map`] for `W` and `H` and [`Source`] for `N` and `M`. Programs of more
+/// than 15 methods or instructions per method need a `P` of
+/// [`WideInsPtr`].
 #[derive(Copy, Clone, Serialize, Deserialize)]
-pub struct Puzzle {
-    pub board: Board,
+pub struct Puzzle<
+    const W: usize = MAP_WIDTH,
+    const H: usize = MAP_HEIGHT,
+    const N: usize = STANDARD_METHODS,
+    const M: usize = STANDARD_METHOD_LENGTH,
+    P: InsPointer = InsPtr,
+> {
+    pub board: Board<W, H>,
     pub stars: usize,
-    pub methods: [usize; 5],
-    pub actual_methods: [usize; 5],
+    #[serde(with = "lengths")]
+    pub methods: [usize; N],
+    #[serde(with = "lengths")]
+    pub actual_methods: [usize; N],
     pub marks: [bool; 3],
     pub red: bool,
     pub green: bool,
     pub blue: bool,
+    /// The stack entries of the programs, wide enough for `N` and `M`.
+    #[serde(skip)]
+    pub pointer: PhantomData<P>,
 }
 
-impl Puzzle {
+impl<const W: usize, const H: usize, const N: usize, const M: usize, P: InsPointer>
+    Puzzle<W, H, N, M, P>
+{
+    /// Fails to compile for programs that `P` can't point into.
+    const POINTER_FITS: () = assert!(N <= P::MAX_METHODS && M <= P::MAX_METHOD_LENGTH);
     pub(crate) fn get_ins_set(&self, colors: Ins, gray: bool) -> Vec<Ins> {
         coz::begin!("get instruction set");
         let functions = self
@@ -50,9 +70,9 @@ impl Puzzle {
             for ins in &MOVES {
                 result.push(*ins | condition);
             }
-            for i in 0..FUNCTIONS.len() {
+            for i in 0..N {
                 if self.methods[i] > 0 {
-                    result.push(FUNCTIONS[i] | condition);
+                    result.push(Ins::fun_from_index(i) | condition);
                 }
             }
             for i in 0..MARKS.len() {
@@ -64,8 +84,8 @@ impl Puzzle {
         coz::end!("get instruction set");
         return result;
     }
-    pub(crate) fn empty_source(&self) -> Source {
-        let mut result = NOGRAM.clone();
+    pub(crate) fn empty_source(&self) -> Source<N, M> {
+        let mut result = Source::halted();
         for instructions in 0..self.methods.len() {
             for i in 0..self.methods[instructions] {
                 result.0[instructions][i] = NOP;
@@ -73,7 +93,8 @@ impl Puzzle {
         }
         return result;
     }
-    pub(crate) fn initial_state(&self, source: &Source) -> State {
+    pub fn initial_state(&self, source: &Source<N, M>) -> State<W, H, P> {
+        let () = Self::POINTER_FITS;
         let mut result = State {
             stars: self.stars,
             board: self.board,
@@ -83,9 +104,9 @@ impl Puzzle {
         return result;
     }
     /// execute a source for the puzzle, returning a score
-    pub fn execute<F, R>(&self, source: &Source, show: bool, mut scoring: F) -> R
+    pub fn execute<F, R>(&self, source: &Source<N, M>, show: bool, mut scoring: F) -> R
     where
-        F: FnMut(&State, &Puzzle) -> R,
+        F: FnMut(&State<W, H, P>, &Puzzle<W, H, N, M, P>) -> R,
     {
         coz::begin!("execute");
         let mut state = self.initial_state(source);
@@ -106,6 +127,25 @@ impl Puzzle {
         coz::end!("execute");
         return result;
     }
+    /// Colors that conditions may test.
+    pub fn color_count(&self) -> usize {
+        [self.red, self.green, self.blue]
+            .iter()
+            .filter(|&&color| color)
+            .count()
+    }
+    /// Colors that may be painted.
+    pub fn mark_count(&self) -> usize {
+        self.marks.iter().filter(|&&mark| mark).count()
+    }
+    /// Methods with at least one slot.
+    pub fn method_count(&self) -> usize {
+        self.methods.iter().filter(|&&length| length > 0).count()
+    }
+    /// Instruction slots of all methods together.
+    pub fn slot_count(&self) -> usize {
+        self.methods.iter().sum()
+    }
     pub(crate) fn get_cond_mask(&self) -> Ins {
         if (self.red as u8) + (self.green as u8) + (self.blue as u8) > 1 {
             with_conds(self.red, self.green, self.blue)
@@ -115,16 +155,22 @@ impl Puzzle {
     }
 }
 
-pub fn make_puzzle(
+pub fn make_puzzle<
+    const W: usize,
+    const H: usize,
+    const N: usize,
+    const M: usize,
+    P: InsPointer,
+>(
     Board {
         map,
         direction,
         x,
         y,
-    }: Board,
-    mut methods: [usize; 5],
+    }: Board<W, H>,
+    mut methods: [usize; N],
     marks: [bool; 3],
-) -> Puzzle {
+) -> Puzzle<W, H, N, M, P> {
     // must be able to use markable colors
     let [mut red, mut green, mut blue] = marks;
     // test which colors are reachable
@@ -144,7 +190,7 @@ pub fn make_puzzle(
         }
     }
     let actual_methods = methods;
-    methods[1..5].sort_unstable_by(|a, b| b.cmp(a));
+    methods[1..].sort_unstable_by(|a, b| b.cmp(a));
     let mut map_out = map.clone();
     map_out.0[y][x].clear_star();
     map_out.0[y][x].touch();
@@ -167,17 +213,24 @@ pub fn make_puzzle(
         red,
         green,
         blue,
+        pointer: PhantomData,
     };
 }
 
-pub(crate) fn verify_puzzle(puzzle: &Puzzle) -> bool {
+pub(crate) fn verify_puzzle<
+    const W: usize,
+    const H: usize,
+    const N: usize,
+    const M: usize,
+    P: InsPointer,
+>(
+    puzzle: &Puzzle<W, H, N, M, P>,
+) -> bool {
     let (mut red, mut green, mut blue) = (false, false, false);
-    for y in 1..13 {
-        for x in 1..17 {
-            red |= puzzle.board.map.0[y][x].is_red();
-            green |= puzzle.board.map.0[y][x].is_green();
-            blue |= puzzle.board.map.0[y][x].is_blue();
-        }
+    for (x, y) in Map::<W, H>::coordinates() {
+        red |= puzzle.board.map.0[y][x].is_red();
+        green |= puzzle.board.map.0[y][x].is_green();
+        blue |= puzzle.board.map.0[y][x].is_blue();
     }
     let stars: usize = puzzle.board.map.count_stars();
     if red != puzzle.red {
@@ -195,7 +248,9 @@ pub(crate) fn verify_puzzle(puzzle: &Puzzle) -> bool {
     return true;
 }
 
-impl Display for Puzzle {
+impl<const W: usize, const H: usize, const N: usize, const M: usize, P: InsPointer> Display
+    for Puzzle<W, H, N, M, P>
+{
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         writeln!(f, "stars: {}", self.stars,)?;
         write!(f, "{{")?;
@@ -233,7 +288,9 @@ impl Display for Puzzle {
     }
 }
 
-impl Debug for Puzzle {
+impl<const W: usize, const H: usize, const N: usize, const M: usize, P: InsPointer> Debug
+    for Puzzle<W, H, N, M, P>
+{
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Puzzle")
             .field("board", &self.board)
@@ -247,3 +304,25 @@ impl Debug for Puzzle {
             .finish()
     }
 }
+
+/// Serde for the method lengths, which serde only derives for fixed sizes.
+mod lengths {
+    use super::*;
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        lengths: &[usize; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(lengths)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[usize; N], D::Error> {
+        let lengths = Vec::<usize>::deserialize(deserializer)?;
+        let length = lengths.len();
+        lengths
+            .try_into()
+            .map_err(|_| de::Error::invalid_length(length, &"a length per method"))
+    }
+}
@@ -7,6 +7,8 @@ use std::{
     hash::{Hash, Hasher},
 };
 
+use serde::Serialize;
+
 use super::board::Board;
 use super::instructions::*;
 use super::puzzle::Puzzle;
@@ -16,67 +18,174 @@ use super::Source;
 use super::Stack;
 use super::Tile;
 use super::TileType;
+use super::{MAP_HEIGHT, MAP_WIDTH};
 use crate::constants::*;
 
+/// Why a [`State`] stopped running.
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Serialize, Debug)]
+pub enum Termination {
+    /// Every star was collected.
+    Won,
+    /// Every instruction was executed with stars left.
+    StackExhausted,
+    /// The robot moved onto an empty tile.
+    FellOff,
+    /// The current tile was touched more often than allowed.
+    TouchLimit,
+    /// The steps given to [`State::steps`] or [`State::stepsj`] or the
+    /// step budget of the [`VmLimits`] ran out.
+    StepLimit,
+    /// A call pushed the stack past the depth allowed by the [`VmLimits`].
+    StackOverflow,
+}
+
+/// Stack entries a [`State`] may hold by default. Every instruction left in
+/// an active call takes an entry, so this bounds the memory of programs that
+/// recurse without returning.
+pub const DEFAULT_MAX_STACK: usize = 1 << 16;
+
+/// Bounds on a running [`State`], exceeding one terminates it.
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug)]
+pub struct VmLimits {
+    pub max_stack: usize,
+    pub max_steps: usize,
+}
+
+impl Default for VmLimits {
+    fn default() -> Self {
+        VmLimits {
+            max_stack: DEFAULT_MAX_STACK,
+            max_steps: usize::MAX,
+        }
+    }
+}
+
+/// How much of the stack [`State::fingerprint`] and [`State::same_position`]
+/// compare. Both always compare the stack length.
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug)]
+pub enum StackMatch {
+    /// Every entry of the stack.
+    Exact,
+    /// Only the given number of entries popped next. Cheaper on deep stacks,
+    /// but states that differ further down count as the same.
+    Top(usize),
+}
+
+impl StackMatch {
+    fn entries<'a, P: InsPointer>(&self, stack: &'a Stack<P>) -> &'a [P] {
+        match *self {
+            StackMatch::Exact => stack.top(stack.len()),
+            StackMatch::Top(count) => stack.top(count),
+        }
+    }
+}
+
+/// A program running on a board, see [`Map`](super::Map) for `W` and `H` and
+/// [`Puzzle`] for `P`.
 #[derive(Eq, PartialEq, Clone)]
-pub struct State {
+pub struct State<const W: usize = MAP_WIDTH, const H: usize = MAP_HEIGHT, P: InsPointer = InsPtr> {
     pub(crate) steps: usize, // number of instructions executed
     pub(crate) stars: usize, // number of stars remaining
-    pub stack: Stack,
-    pub board: Board,
+    pub stack: Stack<P>,
+    pub board: Board<W, H>,
+    pub(crate) termination: Option<Termination>,
+    pub(crate) limits: VmLimits,
 }
 
-impl Default for State {
+impl<const W: usize, const H: usize, P: InsPointer> Default for State<W, H, P> {
     fn default() -> Self {
         State {
             steps: 0,
             stars: 1,
             stack: Default::default(),
             board: Default::default(),
+            termination: None,
+            limits: VmLimits::default(),
         }
     }
 }
 
-impl Hash for State {
-    fn hash<H: Hasher>(&self, state: &mut H) {
+impl<const W: usize, const H: usize, P: InsPointer> Hash for State<W, H, P> {
+    fn hash<T: Hasher>(&self, state: &mut T) {
         self.stars.hash(state);
         self.stack.hash(state);
         self.board.hash(state);
     }
 }
 
-impl State {
-    pub fn initialize(&mut self, source: &Source, puzzle: &Puzzle) {
+impl<const W: usize, const H: usize, P: InsPointer> State<W, H, P> {
+    pub fn initialize<const N: usize, const M: usize>(
+        &mut self,
+        source: &Source<N, M>,
+        puzzle: &Puzzle<W, H, N, M, P>,
+    ) {
         self.invoke(source, puzzle.methods[F1.source_index()], F1.source_index());
+        self.terminate();
     }
     pub fn current_tile(&self) -> &Tile {
         self.board.current_tile()
     }
-    pub fn ins_pointer(&self) -> &InsPtr {
+    pub fn ins_pointer(&self) -> &P {
         let ins = self.stack.last();
         //        let ins = source[ins.get_method_index()][ins.get_ins_index()];
         return ins;
     }
-    pub fn current_ins(&self, source: &Source) -> Ins {
+    pub fn current_ins<const N: usize, const M: usize>(&self, source: &Source<N, M>) -> Ins {
         let ins = self.ins_pointer();
         let ins = source[ins.get_method_index()][ins.get_ins_index()];
         return ins;
     }
+    pub fn limits(&self) -> VmLimits {
+        self.limits
+    }
+    pub fn with_limits(mut self, limits: VmLimits) -> Self {
+        self.limits = limits;
+        self.terminate();
+        self
+    }
+    /// Why the state stopped running, `None` while it is running.
+    pub fn termination(&self) -> Option<Termination> {
+        self.termination
+    }
+    /// Records why the state stopped running, if it did, and returns whether
+    /// it is still running.
+    fn terminate(&mut self) -> bool {
+        self.termination = if self.stars == 0 {
+            Some(Termination::Won)
+        } else if *self.board.current_tile() == _N {
+            Some(Termination::FellOff)
+        } else if self.board.touches() >= Tile::MAX_TOUCHES as usize {
+            Some(Termination::TouchLimit)
+        } else if self.stack.len() > self.limits.max_stack {
+            Some(Termination::StackOverflow)
+        } else if self.stack.is_empty() {
+            Some(Termination::StackExhausted)
+        } else if self.steps >= self.limits.max_steps {
+            Some(Termination::StepLimit)
+        } else {
+            None
+        };
+        self.termination.is_none()
+    }
     pub(crate) fn running(&self) -> bool {
         !self.stack.is_empty()
             && self.stars > 0
             && self.board.touches() < Tile::MAX_TOUCHES as usize
             && *self.board.current_tile() != _N
-        //&& self.stack.len() < StackArr::STACK_SIZE - 10
-        //&& self.steps < MAX_STEPS
+            && self.stack.len() <= self.limits.max_stack
+            && self.steps < self.limits.max_steps
     }
-    pub(crate) fn step(&mut self, source: &Source, puzzle: &Puzzle) -> bool {
+    pub(crate) fn step<const N: usize, const M: usize>(
+        &mut self,
+        source: &Source<N, M>,
+        puzzle: &Puzzle<W, H, N, M, P>,
+    ) -> bool {
         coz::begin!("step");
         let ins = self.current_ins(source).as_vanilla();
         self.stack.pop();
         self.steps += 1;
         if self.board.current_tile().executes(ins) {
-            match ins.get_ins() {
+            match ins.get_opcode() {
                 FORWARD => {
                     self.board.y = (self.board.y as i32
                         + [-1, 0, 1, 0][self.board.direction as usize])
@@ -116,15 +225,16 @@ impl State {
             }
         }
         coz::end!("step");
-        return self.running();
+        return self.terminate();
     }
-    pub fn steps(
+    pub fn steps<const N: usize, const M: usize>(
         &mut self,
-        source: &Source,
-        puzzle: &Puzzle,
+        source: &Source<N, M>,
+        puzzle: &Puzzle<W, H, N, M, P>,
         max_steps: usize,
         max_touches: usize,
     ) -> bool {
+        let step_cap = self.steps + max_steps;
         for step in 0..max_steps {
             let rins = self.current_ins(source);
             let ins = rins.as_vanilla();
@@ -142,7 +252,7 @@ impl State {
             if self.board.current_tile().executes(ins)
                 && self.current_tile().touches() <= max_touches
             {
-                match ins.get_ins() {
+                match ins.get_opcode() {
                     FORWARD => {
                         self.board.y = (self.board.y as i32
                             + [-1, 0, 1, 0][self.board.direction as usize])
@@ -181,22 +291,81 @@ impl State {
                     _ => (),
                 }
             }
-            if !self.running() {
+            if !self.terminate() {
+                break;
+            }
+            if self.current_tile().touches() > max_touches {
+                self.termination = Some(Termination::TouchLimit);
                 break;
             }
         }
-        return self.running();
-    }
-    pub(crate) fn stepsj(&mut self, source: &Source, puzzle: &Puzzle, max_steps: usize) -> bool {
-        next_op(self, source, self.steps + max_steps, puzzle);
-        return self.running();
+        if self.termination.is_none() && self.steps >= step_cap {
+            self.termination = Some(Termination::StepLimit);
+        }
+        return self.termination.is_none();
     }
-
-    pub(crate) fn invoke(&mut self, source: &Source, method_length: usize, method: usize) {
+    pub(crate) fn invoke<const N: usize, const M: usize>(
+        &mut self,
+        source: &Source<N, M>,
+        method_length: usize,
+        method: usize,
+    ) {
         for i in (0..method_length).rev() {
             let ins = source.0[method][i];
-            self.stack.push(InsPtr::new(method, i));
+            self.stack.push(P::new(method, i));
+        }
+    }
+    /// A hash of the stars left, the robot, the colors and stars of the tiles
+    /// and the stack as compared by `matching`. Touch counts are left out, so
+    /// a program that comes back to where it was keeps its fingerprint.
+    /// States that are the [`State::same_position`] have the same fingerprint.
+    pub fn fingerprint(&self, matching: StackMatch) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash_position(matching, &mut hasher);
+        hasher.finish()
+    }
+    /// A [`State::fingerprint`] that also covers the touch counts of the
+    /// tiles if `touches`, from a hasher that took `seed` first. Fingerprints
+    /// of different seeds collide independently of each other.
+    pub fn seeded_fingerprint(&self, matching: StackMatch, touches: bool, seed: u64) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        self.hash_position(matching, &mut hasher);
+        if touches {
+            for tile in self.board.map.0.iter().flatten() {
+                tile.touches().hash(&mut hasher);
+            }
         }
+        hasher.finish()
+    }
+    fn hash_position(&self, matching: StackMatch, hasher: &mut impl Hasher) {
+        self.stars.hash(hasher);
+        (self.board.x, self.board.y, self.board.direction).hash(hasher);
+        let layout = self
+            .board
+            .map
+            .0
+            .map(|row| row.map(|tile| tile.layout().0 as u8));
+        layout.hash(hasher);
+        self.stack.len().hash(hasher);
+        matching.entries(&self.stack).hash(hasher);
+    }
+    /// Whether the two states only differ in steps taken, touch counts and
+    /// the stack entries ignored by `matching`.
+    pub fn same_position(&self, other: &Self, matching: StackMatch) -> bool {
+        self.stars == other.stars
+            && (self.board.x, self.board.y, self.board.direction)
+                == (other.board.x, other.board.y, other.board.direction)
+            && self
+                .board
+                .map
+                .0
+                .iter()
+                .flatten()
+                .zip(other.board.map.0.iter().flatten())
+                .all(|(a, b)| a.layout() == b.layout())
+            && self.stack.len() == other.stack.len()
+            && matching.entries(&self.stack) == matching.entries(&other.stack)
     }
     pub(crate) fn get_hash(&self) -> u64 {
         let mut state = DefaultHasher::new();
@@ -205,6 +374,18 @@ impl State {
     }
 }
 
+// the jump table of `stepsj` is only built for the standard board size
+impl State {
+    pub(crate) fn stepsj(&mut self, source: &Source, puzzle: &Puzzle, max_steps: usize) -> bool {
+        let step_cap = self.steps + max_steps;
+        next_op(self, source, step_cap, puzzle);
+        if self.terminate() && self.steps > step_cap {
+            self.termination = Some(Termination::StepLimit);
+        }
+        return self.termination.is_none();
+    }
+}
+
 const JUMPS: [fn(&mut State, &Source, &Puzzle, Ins, usize); 15] = [
     forward, left, right, funcs, funcs, funcs, funcs, funcs, mark, mark, mark, nop, mark, nop, halt,
 ];
@@ -229,7 +410,7 @@ fn next_op(state: &mut State, source: &Source, max_steps: usize, puzzle: &Puzzle
     }
     state.stack.pop();
     state.steps += 1;
-    let ins_id = (ins.get_ins().0 as usize);
+    let ins_id = (ins.get_opcode().0 as usize);
     return JUMPS[ins_id.min(13) * executes as usize * onwards as usize
         + 13 * !executes as usize * onwards as usize
         + 14 * !onwards as usize](state, source, puzzle, ins, max_steps);
@@ -274,15 +455,21 @@ fn nop(state: &mut State, source: &Source, puzzle: &Puzzle, ins: Ins, max_steps:
 
 fn halt(state: &mut State, source: &Source, puzzle: &Puzzle, ins: Ins, max_steps: usize) {}
 
-pub fn won(state: &State, _: &Puzzle) -> bool {
+pub fn won<const W: usize, const H: usize, const N: usize, const M: usize, P: InsPointer>(
+    state: &State<W, H, P>,
+    _: &Puzzle<W, H, N, M, P>,
+) -> bool {
     return state.stars == 0;
 }
 
-pub fn steps(state: &State, _: &Puzzle) -> usize {
+pub fn steps<const W: usize, const H: usize, const N: usize, const M: usize, P: InsPointer>(
+    state: &State<W, H, P>,
+    _: &Puzzle<W, H, N, M, P>,
+) -> usize {
     return state.steps;
 }
 
-impl Display for State {
+impl<const W: usize, const H: usize, P: InsPointer> Display for State<W, H, P> {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         write!(
             f,
@@ -297,7 +484,7 @@ impl Display for State {
         write!(f, "")
     }
 }
-impl Debug for State {
+impl<const W: usize, const H: usize, P: InsPointer> Debug for State<W, H, P> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         write!(f, "{}", self)
     }
@@ -2,7 +2,14 @@ use rand::seq::SliceRandom;
 use rand::SeedableRng;
 
 use crate::constants::*;
-use crate::game::{instructions::*, state::won, Source};
+use crate::game::board::Board;
+use crate::game::history::{Breakpoint, History};
+use crate::game::notation::{parse_program, ProgramError};
+use crate::game::puzzle::{make_puzzle, verify_puzzle, Puzzle};
+use crate::game::state::{StackMatch, State, Termination, VmLimits};
+use crate::game::verify::{verify, Violation, VERIFY_STEP_CAP};
+use crate::game::{instructions::*, state::steps, state::won, Source, Tile, MAX_STEPS};
+use crate::game::{Direction, Map, MAP_HEIGHT, MAP_WIDTH};
 use crate::solver::backtrack::backtrack;
 use crate::solver::carlo;
 use crate::solver::solutions::read_solution_from_file;
@@ -413,3 +420,411 @@ fn test_cached_solutions() {
         assert!(!level.puzzle.execute(&TEST_SOURCE, false, won));
     }
 }
+
+#[test]
+fn test_notation_round_trip() {
+    for solution in [
+        PUZZLE_42_SOLUTION,
+        PUZZLE_536_SOLUTION,
+        PUZZLE_656_SOLUTION,
+        PUZZLE_1337_SOLUTION,
+        PUZZLE_TEST_1_SOLUTION,
+    ] {
+        let text = solution.notation().to_string();
+        assert_eq!(text.parse::<Source>(), Ok(solution), "{}", text);
+    }
+    assert_eq!(
+        PUZZLE_1337_SOLUTION.notation().to_string(),
+        "F1: 2 Br Gb Rg f 1 | F2: G> B<"
+    );
+}
+
+#[test]
+fn test_notation_parsing() {
+    let solution = parse_program("F1: 2 < 3 < 1 | F2: 3 3 | F3: 4 4 | F4: f f", &PUZZLE_42);
+    assert_eq!(solution, Ok(PUZZLE_42_SOLUTION));
+    let unlabelled = parse_program("2 < 3 < 1 | 3 3 | 4 4 | f f", &PUZZLE_42);
+    assert_eq!(unlabelled, Ok(PUZZLE_42_SOLUTION));
+    assert_eq!(
+        parse_program("F2: f f f", &PUZZLE_42),
+        Err(ProgramError::MethodTooLong {
+            method: 1,
+            length: 3,
+            max: 2
+        })
+    );
+    assert_eq!(
+        parse_program("F1: 5", &PUZZLE_42),
+        Err(ProgramError::UnavailableMethod { method: 4 })
+    );
+    assert_eq!(
+        "F1: f x".parse::<Source>(),
+        Err(ProgramError::UnknownInstruction {
+            method: 0,
+            token: "x".to_string()
+        })
+    );
+    assert_eq!(
+        "F6: f".parse::<Source>(),
+        Err(ProgramError::UnknownMethod("F6".to_string()))
+    );
+    assert_eq!(
+        "F1: f | F1: f".parse::<Source>(),
+        Err(ProgramError::DuplicateMethod(0))
+    );
+}
+
+#[test]
+fn test_trace_events() {
+    let trace = PUZZLE_1337.trace(&PUZZLE_1337_SOLUTION, MAX_STEPS);
+    assert!(trace.won && trace.finished);
+    assert_eq!(
+        trace.events.len(),
+        PUZZLE_1337.execute(&PUZZLE_1337_SOLUTION, false, steps)
+    );
+    let stars = trace.events.iter().filter(|e| e.star_collected).count();
+    assert_eq!(stars, PUZZLE_1337.stars);
+    assert!(trace.events.iter().any(|event| !event.executed));
+    assert!(trace.events.iter().any(|event| event.repainted.is_some()));
+    for (event, next) in trace.events.iter().zip(trace.events.iter().skip(1)) {
+        assert_eq!(event.after, next.before);
+        assert_eq!(event.stack_after, next.stack_before);
+        assert_eq!(event.step + 1, next.step);
+    }
+    assert_eq!(trace.first_divergence(&trace), None);
+    let other = PUZZLE_1337.trace(&TEST_SOURCE, MAX_STEPS);
+    assert_eq!(trace.first_divergence(&other), Some(0));
+
+    let json = serde_json::to_value(&trace).unwrap();
+    assert_eq!(json["events"][0]["instruction"], "F2");
+    assert_eq!(json["events"][0]["before"]["direction"], "Right");
+}
+
+#[test]
+fn test_history_step_back() {
+    for (puzzle, solution) in [
+        (PUZZLE_42, PUZZLE_42_SOLUTION),
+        (PUZZLE_1337, PUZZLE_1337_SOLUTION),
+    ] {
+        let mut history = History::new(&puzzle, solution);
+        let mut states = vec![history.state().clone()];
+        while history.step().is_some() {
+            states.push(history.state().clone());
+        }
+        assert!(won(history.state(), &puzzle));
+        while history.step_back().is_some() {
+            assert_eq!(history.state(), &states[history.position()]);
+            assert_eq!(history.state().board, states[history.position()].board);
+        }
+        assert_eq!(history.position(), 0);
+
+        assert_eq!(history.jump(7), 7);
+        assert_eq!(history.state().board, states[7].board);
+        assert_eq!(history.jump(3), 3);
+        assert_eq!(history.state().board, states[3].board);
+        assert_eq!(history.jump(usize::MAX), states.len() - 1);
+    }
+}
+
+#[test]
+fn test_history_breakpoints() {
+    let mut history = History::new(&PUZZLE_1337, PUZZLE_1337_SOLUTION);
+    history.breakpoints.push(Breakpoint::Star);
+    assert_eq!(history.run(MAX_STEPS), Some(Breakpoint::Star));
+    assert!(history.events().last().unwrap().star_collected);
+
+    let pointer = InsPtr::new(1, 1);
+    history.breakpoints = vec![Breakpoint::Pointer(pointer)];
+    assert_eq!(history.run(MAX_STEPS), Some(Breakpoint::Pointer(pointer)));
+    assert_eq!(*history.state().ins_pointer(), pointer);
+
+    let after = history.events().last().unwrap().after;
+    let tile = Breakpoint::Tile {
+        x: after.x,
+        y: after.y,
+    };
+    history.jump(0);
+    history.breakpoints = vec![tile];
+    assert_eq!(history.run(MAX_STEPS), Some(tile));
+    history.breakpoints.clear();
+    assert_eq!(history.run(MAX_STEPS), None);
+    assert!(history.finished());
+}
+
+#[test]
+fn test_verify() {
+    for (puzzle, solution) in [
+        (PUZZLE_42, PUZZLE_42_SOLUTION),
+        (PUZZLE_1337, PUZZLE_1337_SOLUTION),
+    ] {
+        let report = verify(&puzzle, &solution);
+        assert!(report.passed(), "{}", report);
+        assert_eq!(report.termination, Termination::Won);
+        assert_eq!(report.steps, puzzle.execute(&solution, false, steps));
+    }
+    let empty = verify(&PUZZLE_42, &"F1: >".parse().unwrap());
+    assert_eq!(
+        (empty.termination, empty.stars),
+        (Termination::StackExhausted, 23)
+    );
+    assert!(!empty.passed());
+    let fell = verify(&PUZZLE_42, &"F1: f 1".parse().unwrap());
+    assert_eq!(fell.termination, Termination::FellOff);
+    let spinning = verify(&PUZZLE_42, &"F1: 2 | F2: > 2".parse().unwrap());
+    assert_eq!(spinning.termination, Termination::StepLimit);
+    assert_eq!(spinning.steps, VERIFY_STEP_CAP);
+
+    let mut unfit = PUZZLE_42_SOLUTION;
+    unfit[4][0] = RED_COND | MARK_GREEN;
+    unfit[3][2] = F5;
+    assert_eq!(
+        verify(&PUZZLE_42, &unfit).violations,
+        vec![
+            Violation::MethodTooLong {
+                method: 3,
+                length: 3,
+                max: 2
+            },
+            Violation::UnavailableMethod {
+                method: 3,
+                index: 2
+            },
+            Violation::MethodTooLong {
+                method: 4,
+                length: 1,
+                max: 0
+            },
+            Violation::UnavailableMark {
+                method: 4,
+                index: 0
+            },
+            Violation::UnavailableColor {
+                method: 4,
+                index: 0
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_termination() {
+    let mut state = PUZZLE_1337.initial_state(&PUZZLE_1337_SOLUTION);
+    assert_eq!(state.termination(), None);
+    while state.step(&PUZZLE_1337_SOLUTION, &PUZZLE_1337) {}
+    assert_eq!(state.termination(), Some(Termination::Won));
+
+    // the backtracker's stepping stops at instructions that aren't loosened
+    let loosened = |text: &str| {
+        let mut source: Source = text.parse().unwrap();
+        for ins in source.0.iter_mut().flatten().filter(|ins| !ins.is_halt()) {
+            *ins = ins.as_loosened();
+        }
+        source
+    };
+    let source = loosened("F1: > 1");
+    let mut state = PUZZLE_42.initial_state(&source);
+    assert!(!state.steps(&source, &PUZZLE_42, 100, Tile::MAX_TOUCHES as usize));
+    assert_eq!(state.termination(), Some(Termination::StepLimit));
+    let mut state = PUZZLE_42.initial_state(&source);
+    assert!(!state.steps(&source, &PUZZLE_42, 100, 10));
+    assert_eq!(state.termination(), Some(Termination::TouchLimit));
+    let mut state = PUZZLE_42.initial_state(&source);
+    assert!(!state.stepsj(&source, &PUZZLE_42, 100));
+    assert_eq!(state.termination(), Some(Termination::StepLimit));
+
+    let source = loosened("F1: f 1");
+    let mut state = PUZZLE_42.initial_state(&source);
+    assert!(!state.stepsj(&source, &PUZZLE_42, 100));
+    assert_eq!(state.termination(), Some(Termination::FellOff));
+    let source = loosened("F1: >");
+    let mut state = PUZZLE_42.initial_state(&source);
+    assert!(!state.steps(&source, &PUZZLE_42, 100, Tile::MAX_TOUCHES as usize));
+    assert_eq!(state.termination(), Some(Termination::StackExhausted));
+
+    let mut history = History::new(&PUZZLE_1337, PUZZLE_1337_SOLUTION);
+    history.jump(usize::MAX);
+    assert_eq!(history.state().termination(), Some(Termination::Won));
+    history.step_back();
+    assert_eq!(history.state().termination(), None);
+}
+
+#[test]
+fn test_vm_limits() {
+    let limits = VmLimits {
+        max_stack: 64,
+        max_steps: 1000,
+    };
+    let recursive: Source = "F1: 1 f".parse().unwrap();
+    let mut state = PUZZLE_42.initial_state(&recursive).with_limits(limits);
+    while state.step(&recursive, &PUZZLE_42) {}
+    assert_eq!(state.termination(), Some(Termination::StackOverflow));
+    assert!(state.stack.len() <= limits.max_stack + PUZZLE_42.methods[0]);
+
+    // F2 fills its slots, so its call is a tail call
+    let spinning: Source = "F1: 2 | F2: > 2".parse().unwrap();
+    let mut state = PUZZLE_42.initial_state(&spinning).with_limits(limits);
+    while state.step(&spinning, &PUZZLE_42) {}
+    assert_eq!(state.termination(), Some(Termination::StepLimit));
+    assert_eq!(state.steps, limits.max_steps);
+
+    let state = PUZZLE_42
+        .initial_state(&PUZZLE_42_SOLUTION)
+        .with_limits(VmLimits {
+            max_stack: 1,
+            ..limits
+        });
+    assert_eq!(state.termination(), Some(Termination::StackOverflow));
+}
+
+#[test]
+fn test_fingerprint() {
+    // short stacks hash their whole stack
+    let state = PUZZLE_42.initial_state(&PUZZLE_42_SOLUTION);
+    assert!(state.stack.len() < 64);
+    assert_eq!(state.get_hash(), state.clone().get_hash());
+
+    // each call of F2 turns right, after four calls the robot is back
+    let spinning: Source = "F1: 2 | F2: > 2".parse().unwrap();
+    let mut state = PUZZLE_42.initial_state(&spinning);
+    while state.ins_pointer().get_method_index() != 1 {
+        state.step(&spinning, &PUZZLE_42);
+    }
+    let start = state.clone();
+    let mut turns = vec![];
+    for _ in 0..8 {
+        state.step(&spinning, &PUZZLE_42);
+        turns.push(state.fingerprint(StackMatch::Exact));
+    }
+    assert!(start.same_position(&state, StackMatch::Exact));
+    assert_eq!(start.fingerprint(StackMatch::Exact), turns[7]);
+    assert!(turns[..7].iter().all(|&f| f != turns[7]));
+
+    let mut deeper = state.clone();
+    deeper.stack.0.insert(0, InsPtr::new(2, 0));
+    assert!(!state.same_position(&deeper, StackMatch::Top(4)));
+    let mut different = deeper.clone();
+    different.stack.0[0] = InsPtr::new(3, 0);
+    assert!(deeper.same_position(&different, StackMatch::Top(1)));
+    assert_eq!(
+        deeper.fingerprint(StackMatch::Top(1)),
+        different.fingerprint(StackMatch::Top(1))
+    );
+    assert!(!deeper.same_position(&different, StackMatch::Exact));
+    assert_ne!(
+        deeper.fingerprint(StackMatch::Exact),
+        different.fingerprint(StackMatch::Exact)
+    );
+}
+
+#[test]
+fn test_custom_board_size() {
+    // a corridor wider than the standard boards with a star at its end
+    let mut map = Map::<42, 5>::empty();
+    for (x, y) in Map::<42, 5>::coordinates().filter(|&(_, y)| y == 2) {
+        map.0[y][x] = if x == Map::<42, 5>::WIDTH { RS } else { RE };
+    }
+    let board = Board {
+        map,
+        direction: Direction::Right,
+        x: 1,
+        y: 2,
+    };
+    let puzzle: Puzzle<42, 5> = make_puzzle(board, [2, 0, 0, 0, 0], [false; 3]);
+    assert_eq!(puzzle.stars, 1);
+    assert!(verify_puzzle(&puzzle));
+    assert_eq!(puzzle.board.count_tiles(), 40);
+
+    let walker: Source = "F1: f 1".parse().unwrap();
+    assert!(puzzle.execute(&walker, false, won));
+    // 39 moves and the 38 calls between them
+    assert_eq!(puzzle.execute(&walker, false, steps), 77);
+    let mut state = puzzle.initial_state(&walker);
+    while state.step(&walker, &puzzle) {}
+    assert_eq!(state.board.x, Map::<42, 5>::WIDTH);
+
+    // walking off the board lands on the border
+    let board = Board {
+        direction: Direction::Left,
+        ..board
+    };
+    let puzzle: Puzzle<42, 5> = make_puzzle(board, [2, 0, 0, 0, 0], [false; 3]);
+    let mut state = puzzle.initial_state(&walker);
+    while state.step(&walker, &puzzle) {}
+    assert_eq!(state.termination(), Some(Termination::FellOff));
+
+    let json = serde_json::to_string(&puzzle.board.map).unwrap();
+    assert_eq!(
+        serde_json::from_str::<Map<42, 5>>(&json).unwrap(),
+        puzzle.board.map
+    );
+    assert!(serde_json::from_str::<Map>(&json).is_err());
+    let standard = PUZZLE_42.board.map;
+    let json = serde_json::to_string(&standard).unwrap();
+    assert_eq!(serde_json::from_str::<Map>(&json).unwrap(), standard);
+}
+
+#[test]
+fn test_wide_program() {
+    assert_eq!(
+        FUNCTIONS.map(|f| Ins::fun_from_index(f.source_index())),
+        FUNCTIONS
+    );
+    // standard programs keep a byte per stack entry
+    assert_eq!(std::mem::size_of::<InsPtr>(), 1);
+    assert!(
+        std::mem::size_of::<State>()
+            < std::mem::size_of::<State<MAP_WIDTH, MAP_HEIGHT, WideInsPtr>>()
+    );
+    let pointer = WideInsPtr::new(MAX_METHODS - 1, MAX_METHOD_LENGTH - 1);
+    assert_eq!(
+        (pointer.get_method_index(), pointer.get_ins_index()),
+        (MAX_METHODS - 1, MAX_METHOD_LENGTH - 1)
+    );
+    assert_ne!(pointer, *WideInsPtr::NULL);
+    for index in 0..MAX_METHODS {
+        let call = Ins::fun_from_index(index) | BLUE_COND;
+        assert!(call.is_function() && !call.is_mark() && !call.is_debug());
+        assert_eq!((call.source_index(), call.get_cond()), (index, BLUE_COND));
+        assert_eq!(call.as_loosened().as_vanilla(), call);
+    }
+
+    // a corridor walked by a chain of twelve methods
+    let mut map = Map::empty();
+    for x in 1..=13 {
+        map.0[2][x] = if x == 13 { RS } else { RE };
+    }
+    let board = Board {
+        map,
+        direction: Direction::Right,
+        x: 1,
+        y: 2,
+    };
+    let puzzle =
+        make_puzzle::<MAP_WIDTH, MAP_HEIGHT, 12, 20, WideInsPtr>(board, [2; 12], [false; 3]);
+    let text = (1..=12)
+        .map(|m| match m {
+            12 => format!("F{}: f", m),
+            _ => format!("F{}: f {}", m, m + 1),
+        })
+        .collect::<Vec<_>>()
+        .join(" | ");
+    let chain: Source<12, 20> = text.parse().unwrap();
+    assert_eq!(chain.notation().to_string(), text);
+    assert_eq!(parse_program(&text, &puzzle), Ok(chain));
+    assert!(puzzle.execute(&chain, false, won));
+    // 12 moves and the 11 calls between them
+    assert_eq!(puzzle.execute(&chain, false, steps), 23);
+    assert!(verify(&puzzle, &chain).passed());
+
+    let set = puzzle.get_ins_set(GRAY_COND, true);
+    assert_eq!(set.len(), MOVES.len() + 12);
+    assert!(set.contains(&Ins::fun_from_index(11)));
+    assert_eq!(
+        "F13: f".parse::<Source<12, 20>>(),
+        Err(ProgramError::UnknownMethod("F13".to_string()))
+    );
+    assert_eq!(
+        "F1: B13".parse::<Source<12, 20>>(),
+        Err(ProgramError::UnavailableMethod { method: 12 })
+    );
+}
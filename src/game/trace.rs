@@ -0,0 +1,165 @@
+use serde::{Serialize, Serializer};
+
+use super::instructions::*;
+use super::puzzle::Puzzle;
+use super::state::{State, Termination};
+use super::{Direction, Source, Tile};
+use crate::constants::*;
+
+/// Where the robot stands and where it is facing, in board coordinates.
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Serialize, Debug)]
+pub struct Position {
+    pub x: usize,
+    pub y: usize,
+    pub direction: Direction,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Serialize, Debug)]
+pub enum TileColor {
+    Red,
+    Green,
+    Blue,
+}
+
+impl TileColor {
+    fn of(tile: &Tile) -> Option<TileColor> {
+        match tile.color() {
+            RE => Some(TileColor::Red),
+            GE => Some(TileColor::Green),
+            BE => Some(TileColor::Blue),
+            _ => None,
+        }
+    }
+    pub(crate) fn to_tile(color: Option<TileColor>) -> Tile {
+        match color {
+            Some(TileColor::Red) => RE,
+            Some(TileColor::Green) => GE,
+            Some(TileColor::Blue) => BE,
+            None => _N,
+        }
+    }
+}
+
+/// Everything a single [`State::step`] did.
+#[derive(Eq, PartialEq, Clone, Serialize, Debug)]
+pub struct StepEvent {
+    /// Number of steps executed before this one.
+    pub step: usize,
+    /// The instruction pointer that was popped, as indices into the source.
+    pub method: usize,
+    pub index: usize,
+    #[serde(serialize_with = "serialize_ins")]
+    pub instruction: Ins,
+    /// False if the condition of the instruction didn't match the tile.
+    pub executed: bool,
+    pub before: Position,
+    pub after: Position,
+    /// Whether the tile under the robot afterwards was touched by this step.
+    pub touched: bool,
+    pub star_collected: bool,
+    /// The (old, new) color of a tile that was marked.
+    pub repainted: Option<(Option<TileColor>, Option<TileColor>)>,
+    pub stack_before: usize,
+    pub stack_after: usize,
+}
+
+fn serialize_ins<S: Serializer>(ins: &Ins, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{:?}", ins))
+}
+
+/// The events of running a program from the initial state of a puzzle.
+#[derive(Clone, Serialize, Debug)]
+pub struct Trace {
+    pub events: Vec<StepEvent>,
+    pub stars: usize,
+    pub won: bool,
+    /// False if the program was cut off by the step limit.
+    pub finished: bool,
+    pub termination: Option<Termination>,
+}
+
+impl Trace {
+    /// The index of the first step where the two programs move the robot or
+    /// change the board differently, if any.
+    pub fn first_divergence(&self, other: &Trace) -> Option<usize> {
+        let effect = |event: &StepEvent| {
+            (
+                event.before,
+                event.after,
+                event.star_collected,
+                event.repainted,
+            )
+        };
+        let divergence = self
+            .events
+            .iter()
+            .zip(other.events.iter())
+            .position(|(a, b)| effect(a) != effect(b));
+        match divergence {
+            Some(step) => Some(step),
+            None if self.events.len() != other.events.len() => {
+                Some(self.events.len().min(other.events.len()))
+            }
+            None => None,
+        }
+    }
+}
+
+impl State {
+    pub fn position(&self) -> Position {
+        Position {
+            x: self.board.x,
+            y: self.board.y,
+            direction: self.board.direction,
+        }
+    }
+    /// [`State::step`] that records what happened, the state must be running.
+    pub fn traced_step(&mut self, source: &Source, puzzle: &Puzzle) -> StepEvent {
+        let pointer = *self.ins_pointer();
+        let instruction = self.current_ins(source);
+        let executed = self.current_tile().executes(instruction.as_vanilla());
+        let (step, stars, before, stack_before) =
+            (self.steps, self.stars, self.position(), self.stack.len());
+        let color = TileColor::of(self.current_tile());
+        let touches = self.current_tile().touches();
+        self.step(source, puzzle);
+        let moved = executed && instruction.get_ins() == FORWARD;
+        let new_color = TileColor::of(self.current_tile());
+        StepEvent {
+            step,
+            method: pointer.get_method_index(),
+            index: pointer.get_ins_index(),
+            instruction,
+            executed,
+            before,
+            after: self.position(),
+            touched: if moved {
+                *self.current_tile() != _N
+            } else {
+                self.current_tile().touches() > touches
+            },
+            star_collected: self.stars < stars,
+            repainted: (!moved && new_color != color).then_some((color, new_color)),
+            stack_before,
+            stack_after: self.stack.len(),
+        }
+    }
+}
+
+impl Puzzle {
+    /// Runs `source` for at most `max_steps` steps and records every step.
+    pub fn trace(&self, source: &Source, max_steps: usize) -> Trace {
+        let mut state = self.initial_state(source);
+        let mut events = vec![];
+        while state.running() && events.len() < max_steps {
+            events.push(state.traced_step(source, self));
+        }
+        Trace {
+            events,
+            stars: state.stars,
+            won: state.stars == 0,
+            finished: !state.running(),
+            termination: state.termination(),
+        }
+    }
+}
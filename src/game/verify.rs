@@ -0,0 +1,171 @@
+use std::fmt::{Display, Error, Formatter};
+
+use super::instructions::*;
+use super::puzzle::Puzzle;
+use super::state::{Termination, VmLimits};
+use super::Source;
+use crate::constants::*;
+
+/// Steps [`verify`] runs a program for before giving up on it.
+pub const VERIFY_STEP_CAP: usize = 1 << 20;
+
+/// A way in which a program doesn't fit the puzzle it is run on.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum Violation {
+    MethodTooLong {
+        method: usize,
+        length: usize,
+        max: usize,
+    },
+    UnavailableMethod {
+        method: usize,
+        index: usize,
+    },
+    UnavailableMark {
+        method: usize,
+        index: usize,
+    },
+    UnavailableColor {
+        method: usize,
+        index: usize,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct VerifyReport {
+    pub won: bool,
+    pub steps: usize,
+    pub stars: usize,
+    pub termination: Termination,
+    pub violations: Vec<Violation>,
+}
+
+impl VerifyReport {
+    /// Whether the program wins and fits the puzzle.
+    pub fn passed(&self) -> bool {
+        self.won && self.violations.is_empty()
+    }
+}
+
+/// Runs `source` on `puzzle` and checks it against the method lengths, marks
+/// and colors of the puzzle.
+pub fn verify<const W: usize, const H: usize, const N: usize, const M: usize, P: InsPointer>(
+    puzzle: &Puzzle<W, H, N, M, P>,
+    source: &Source<N, M>,
+) -> VerifyReport {
+    let mut state = puzzle.initial_state(source).with_limits(VmLimits {
+        max_steps: VERIFY_STEP_CAP,
+        ..VmLimits::default()
+    });
+    while state.step(source, puzzle) {}
+    let termination = state.termination().expect("the state stopped");
+    VerifyReport {
+        won: termination == Termination::Won,
+        steps: state.steps,
+        stars: state.stars,
+        termination,
+        violations: violations(puzzle, source),
+    }
+}
+
+fn violations<const W: usize, const H: usize, const N: usize, const M: usize, P: InsPointer>(
+    puzzle: &Puzzle<W, H, N, M, P>,
+    source: &Source<N, M>,
+) -> Vec<Violation> {
+    let colors = [
+        (RED_COND, puzzle.red),
+        (GREEN_COND, puzzle.green),
+        (BLUE_COND, puzzle.blue),
+    ];
+    let mut result = vec![];
+    for method in 0..N {
+        let length = source[method]
+            .iter()
+            .rposition(|ins| !ins.is_halt())
+            .map_or(0, |i| i + 1);
+        if length > puzzle.methods[method] {
+            result.push(Violation::MethodTooLong {
+                method,
+                length,
+                max: puzzle.methods[method],
+            });
+        }
+        for (index, &ins) in source[method].iter().enumerate().take(length) {
+            if ins.is_halt() {
+                continue;
+            }
+            if ins.is_function() && puzzle.methods[ins.source_index()] == 0 {
+                result.push(Violation::UnavailableMethod { method, index });
+            }
+            if ins.is_mark()
+                && !MARKS
+                    .iter()
+                    .zip(puzzle.marks)
+                    .any(|(&mark, allowed)| allowed && mark == ins.get_ins())
+            {
+                result.push(Violation::UnavailableMark { method, index });
+            }
+            if !ins.is_gray()
+                && !colors
+                    .iter()
+                    .any(|&(cond, allowed)| allowed && ins.get_cond() == cond)
+            {
+                result.push(Violation::UnavailableColor { method, index });
+            }
+        }
+    }
+    result
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            Violation::MethodTooLong {
+                method,
+                length,
+                max,
+            } => write!(
+                f,
+                "F{} has {} instructions but the puzzle allows {}",
+                method + 1,
+                length,
+                max
+            ),
+            Violation::UnavailableMethod { method, index } => write!(
+                f,
+                "F{}[{}] calls a method that is not available in this puzzle",
+                method + 1,
+                index
+            ),
+            Violation::UnavailableMark { method, index } => write!(
+                f,
+                "F{}[{}] paints a color that is not available in this puzzle",
+                method + 1,
+                index
+            ),
+            Violation::UnavailableColor { method, index } => write!(
+                f,
+                "F{}[{}] has a condition on a color that is not in this puzzle",
+                method + 1,
+                index
+            ),
+        }
+    }
+}
+
+impl Display for VerifyReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        writeln!(
+            f,
+            "{} after {} steps with {} stars left, stopped: {:?}",
+            if self.won { "won" } else { "lost" },
+            self.steps,
+            self.stars,
+            self.termination
+        )?;
+        for violation in self.violations.iter() {
+            writeln!(f, "violation: {}", violation)?;
+        }
+        Ok(())
+    }
+}
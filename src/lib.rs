@@ -2,7 +2,9 @@
 #![allow(dead_code)]
 #![allow(unused)]
 
+pub mod collection;
 pub mod constants;
+pub mod debugger;
 pub mod game;
 pub mod solver;
 pub mod web;
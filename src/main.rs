@@ -7,25 +7,36 @@
 #![allow(unreachable_code)]
 
 use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::time::{Duration, Instant};
 
+use clap::error::ErrorKind;
 use clap::{value_parser, Arg, ArgAction, Command};
 
 use colored::Colorize;
+use solver::collection::{
+    correlations, correlations_csv, features_csv, puzzle_table, Bounds, ListedPuzzle,
+    PuzzleFeatures, PuzzleQuery, SortKey,
+};
 use solver::constants::*;
+use solver::debugger;
+use solver::game::verify::verify;
 use solver::game::{instructions::*, *};
-use solver::solver::backtrack::{self, backtrack};
+use solver::solver::backtrack::{self, backtrack, BacktrackConfig};
 use solver::solver::carlo::{score, score_cmp};
+use solver::solver::optimal::{optimal, Objective, Optimality};
 use solver::solver::solutions::{
-    read_solution_from_file, remove_solution_file, store_solutions_locally,
+    check_solutions, has_solution_file, read_solution_from_file, remove_solution_file,
+    store_solutions_locally, store_stats_locally,
 };
 use solver::solver::{
     pruning::{banned_pair, banned_trio},
-    solve,
+    solve, solver_by_name, solvers, Progress, ProgressCallback, SolverConfig,
 };
+use solver::web::credentials::{CredentialProvider, DefaultCredentials};
 use solver::web::{
     self, encode_program, get_all_local_levels, get_level, get_levels, puzzle_from_string,
-    solve_puzzle,
+    read_program, solve_puzzle,
 };
 
 fn cli() -> Command {
@@ -62,19 +73,142 @@ fn cli() -> Command {
                 .subcommand(
                     Command::new("solve")
                         .arg_required_else_help(true)
-                        .arg(Arg::new("puzzle ID").value_parser(0..30000)),
+                        .arg(Arg::new("puzzle ID").value_parser(0..30000))
+                        .arg(
+                            Arg::new("login")
+                                .long("login")
+                                .help("Sign in with the credentials from ROBOZZLE_USERNAME and ROBOZZLE_PASSWORD or the ROBOZZLE_CREDENTIALS file")
+                                .action(ArgAction::SetTrue),
+                        ),
                 ),
         )
         .subcommand(
             Command::new("puzzles")
                 .subcommand_required(true)
                 .subcommand(
-                    Command::new("list").arg(
-                        Arg::new("long")
-                            .long("long")
-                            .short('l')
-                            .action(ArgAction::SetTrue),
-                    ),
+                    Command::new("list")
+                        .about("List the local puzzles matching all of the given filters")
+                        .arg(
+                            Arg::new("long")
+                                .long("long")
+                                .short('l')
+                                .help("Also print the board of each puzzle")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("ids")
+                                .long("ids")
+                                .help("Puzzle id or inclusive range such as 100..200, 100.. or ..200")
+                                .value_parser(value_parser!(Bounds)),
+                        )
+                        .arg(
+                            Arg::new("title")
+                                .long("title")
+                                .help("Case-insensitive part of the title"),
+                        )
+                        .arg(
+                            Arg::new("author")
+                                .long("author")
+                                .help("Case-insensitive part of the submitter's name"),
+                        )
+                        .arg(
+                            Arg::new("featured")
+                                .long("featured")
+                                .value_parser(value_parser!(bool)),
+                        )
+                        .arg(
+                            Arg::new("colors")
+                                .long("colors")
+                                .help("Number or range of tile colors")
+                                .value_parser(value_parser!(Bounds)),
+                        )
+                        .arg(
+                            Arg::new("marks")
+                                .long("marks")
+                                .help("Number or range of colors the robot may mark")
+                                .value_parser(value_parser!(Bounds)),
+                        )
+                        .arg(
+                            Arg::new("slots")
+                                .long("slots")
+                                .help("Number or range of instruction slots over all methods")
+                                .value_parser(value_parser!(Bounds)),
+                        )
+                        .arg(
+                            Arg::new("solved")
+                                .long("solved")
+                                .help("Only puzzles with cached solutions")
+                                .conflicts_with("unsolved")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("unsolved")
+                                .long("unsolved")
+                                .help("Only puzzles without cached solutions")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("sort")
+                                .long("sort")
+                                .short('s')
+                                .value_parser(value_parser!(SortKey))
+                                .help("Sort by id, title, difficulty, likes, slots or stars"),
+                        )
+                        .arg(
+                            Arg::new("reverse")
+                                .long("reverse")
+                                .short('r')
+                                .help("Sort in descending order")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("format")
+                                .long("format")
+                                .short('f')
+                                .action(ArgAction::Set)
+                                .value_parser(["plain", "table", "json"])
+                                .default_value("plain"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("stats")
+                        .about("Features of every local puzzle and how they correlate with the difficulty votes")
+                        .arg(
+                            Arg::new("format")
+                                .long("format")
+                                .short('f')
+                                .action(ArgAction::Set)
+                                .value_parser(["csv", "json"])
+                                .default_value("csv"),
+                        )
+                        .arg(
+                            Arg::new("correlations")
+                                .long("correlations")
+                                .help("Print the correlations instead of the features of each puzzle as CSV")
+                                .action(ArgAction::SetTrue),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("solutions")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("check")
+                        .about("Replay every cached solution against its puzzle")
+                        .arg(
+                            Arg::new("fix")
+                                .long("fix")
+                                .help("Prune invalid solutions and remove files of unknown puzzles")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("threads")
+                                .long("threads")
+                                .short('j')
+                                .help("Checker threads, defaults to every core")
+                                .action(ArgAction::Set)
+                                .value_parser(value_parser!(usize)),
+                        ),
                 ),
         )
         .subcommand(
@@ -102,6 +236,53 @@ fn cli() -> Command {
                         .action(ArgAction::Set)
                         .value_parser(value_parser!(u128)),
                 )
+                .arg(
+                    Arg::new("progress")
+                        .long("progress")
+                        .short('p')
+                        .global(true)
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("threads")
+                        .long("threads")
+                        .short('j')
+                        .global(true)
+                        .help("Worker threads of parallel solvers, defaults to every core")
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("transpositions")
+                        .long("transpositions")
+                        .global(true)
+                        .help("Skip explored backtracking frames and cut off looping programs")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("tune")
+                        .long("tune")
+                        .global(true)
+                        .help("Backtracking parameters as name=value pairs, e.g. touch_scale=3,cap_factor=2")
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(BacktrackConfig)),
+                )
+                .arg(
+                    Arg::new("optimal")
+                        .long("optimal")
+                        .global(true)
+                        .help("Prove the best backtracking solution optimal within the step cap")
+                        .action(ArgAction::Set)
+                        .value_parser(["instructions", "steps"]),
+                )
+                .arg(
+                    Arg::new("live")
+                        .long("live")
+                        .short('l')
+                        .global(true)
+                        .help("Print backtracking solutions as they are found, only with the backtrack solver")
+                        .action(ArgAction::SetTrue),
+                )
                 .arg(
                     Arg::new("cache")
                         .long("cache")
@@ -109,6 +290,20 @@ fn cli() -> Command {
                         .global(true)
                         .action(ArgAction::SetTrue),
                 )
+                .arg(
+                    Arg::new("solver")
+                        .long("solver")
+                        .short('s')
+                        .global(true)
+                        .action(ArgAction::Set)
+                        .default_value("backtrack")
+                        .value_parser(
+                            solvers()
+                                .iter()
+                                .map(|solver| solver.name())
+                                .collect::<Vec<_>>(),
+                        ),
+                )
                 .subcommand(
                     Command::new("range").arg(
                         Arg::new("puzzle ID")
@@ -124,6 +319,37 @@ fn cli() -> Command {
                         .value_parser(0..30000),
                 ),
         )
+        .subcommand(
+            Command::new("debug")
+                .about("Step through a program on a puzzle in the terminal")
+                .arg(
+                    Arg::new("puzzle ID")
+                        .required(true)
+                        .value_parser(0..30000),
+                )
+                .arg(
+                    Arg::new("program")
+                        .long("program")
+                        .short('p')
+                        .required(true)
+                        .help("The program in the plain notation, or a robozzle URL or its program code")
+                        .action(ArgAction::Set),
+                ),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check that a program solves a puzzle, exits nonzero if it doesn't")
+                .arg(
+                    Arg::new("puzzle ID")
+                        .required(true)
+                        .value_parser(0..30000),
+                )
+                .arg(
+                    Arg::new("program")
+                        .required(true)
+                        .help("The program in the plain notation, or a robozzle URL or its program code"),
+                ),
+        )
         .subcommand(
             Command::new("misc")
                 .subcommand_negates_reqs(true)
@@ -149,7 +375,13 @@ fn main() {
         Some(("web", matches)) => match matches.subcommand() {
             Some(("solve", matches)) => {
                 let puzzle_id = *matches.get_one::<i64>("puzzle ID").expect("required");
-                solve_puzzle(puzzle_id as u64, false).expect("couldn't solve puzzle");
+                let credentials = matches
+                    .get_flag("login")
+                    .then(|| DefaultCredentials::default().credentials())
+                    .transpose()
+                    .unwrap_or_else(|err| panic!("couldn't sign in: {:?}", err));
+                solve_puzzle(puzzle_id as u64, credentials.as_ref())
+                    .expect("couldn't solve puzzle");
             }
             Some(("fetch", matches)) => {
                 let puzzle_ids: Vec<i64> = matches
@@ -187,8 +419,40 @@ fn main() {
         },
         Some(("puzzles", matches)) => match matches.subcommand() {
             Some(("list", matches)) => {
-                for level in get_all_local_levels() {
-                    print_level(&level, matches.get_flag("long"));
+                let bounds = |name| matches.get_one::<Bounds>(name).copied().unwrap_or_default();
+                let query = PuzzleQuery {
+                    ids: bounds("ids"),
+                    title: matches.get_one::<String>("title").cloned(),
+                    author: matches.get_one::<String>("author").cloned(),
+                    featured: matches.get_one::<bool>("featured").copied(),
+                    colors: bounds("colors"),
+                    marks: bounds("marks"),
+                    slots: bounds("slots"),
+                    solved: if matches.get_flag("solved") {
+                        Some(true)
+                    } else if matches.get_flag("unsolved") {
+                        Some(false)
+                    } else {
+                        None
+                    },
+                    sort: matches.get_one::<SortKey>("sort").copied(),
+                    descending: matches.get_flag("reverse"),
+                };
+                let levels = query.select(get_all_local_levels(), has_solution_file);
+                let listed = || {
+                    levels
+                        .iter()
+                        .map(|level| ListedPuzzle::new(level, has_solution_file(level.id)))
+                        .collect::<Vec<_>>()
+                };
+                match matches.get_one::<String>("format").map(String::as_str) {
+                    Some("json") => println!("{}", serde_json::json!(listed())),
+                    Some("table") => print!("{}", puzzle_table(&listed())),
+                    _ => {
+                        for level in levels.iter() {
+                            print_level(level, matches.get_flag("long"));
+                        }
+                    }
                 }
                 // face = 5088
                 // ternary = 10459
@@ -199,6 +463,68 @@ fn main() {
                 // center_cut = 12684
                 // writers_block = 14874
             }
+            Some(("stats", matches)) => {
+                let mut features: Vec<_> = get_all_local_levels()
+                    .map(|level| PuzzleFeatures::from_caches(&level))
+                    .collect();
+                features.sort_unstable_by_key(|puzzle| puzzle.id);
+                let correlations = correlations(&features);
+                match matches.get_one::<String>("format").map(String::as_str) {
+                    Some("json") => println!(
+                        "{}",
+                        serde_json::json!({
+                            "puzzles": features,
+                            "correlations": correlations,
+                        })
+                    ),
+                    _ if matches.get_flag("correlations") => {
+                        print!("{}", correlations_csv(&correlations))
+                    }
+                    _ => print!("{}", features_csv(&features)),
+                }
+            }
+            _ => todo!(),
+        },
+        Some(("solutions", matches)) => match matches.subcommand() {
+            Some(("check", matches)) => {
+                let fix = matches.get_flag("fix");
+                let threads = matches
+                    .get_one::<usize>("threads")
+                    .copied()
+                    .unwrap_or_else(|| {
+                        std::thread::available_parallelism().map_or(1, |threads| threads.get())
+                    });
+                let checks = check_solutions(Path::new("data/solutions"), fix, threads);
+                let failed: Vec<_> = checks.iter().filter(|check| !check.passed()).collect();
+                for check in failed.iter() {
+                    if let Some(err) = &check.error {
+                        println!("{}: error: {:?}", check.puzzle_id, err);
+                    }
+                    if check.orphaned {
+                        println!("{}: no such puzzle", check.puzzle_id);
+                    }
+                    for (index, report) in check.invalid.iter() {
+                        print!("{}: solution {}: {}", check.puzzle_id, index, report);
+                    }
+                }
+                let invalid: usize = checks.iter().map(|check| check.invalid.len()).sum();
+                println!(
+                    "checked {} files with {} solutions, {} invalid solutions, {} orphaned files{}",
+                    checks.len(),
+                    checks.iter().map(|check| check.solutions).sum::<usize>(),
+                    invalid,
+                    checks.iter().filter(|check| check.orphaned).count(),
+                    if fix && !failed.is_empty() {
+                        ", fixed"
+                    } else {
+                        ""
+                    }
+                );
+                if !failed.is_empty() && (!fix || failed.iter().any(|check| check.error.is_some()))
+                {
+                    std::process::exit(1);
+                }
+            }
             _ => todo!(),
         },
         Some(("backtrack", matches)) => {
@@ -215,7 +541,43 @@ fn main() {
             let timed = matches.get_flag("timed");
             let quiet = matches.get_flag("quiet");
             let cache = matches.get_flag("cache");
+            let live = matches.get_flag("live");
+            let objective =
+                matches
+                    .get_one::<String>("optimal")
+                    .map(|objective| match objective.as_str() {
+                        "steps" => Objective::Steps,
+                        _ => Objective::Instructions,
+                    });
             let timeout = matches.get_one::<u128>("timeout").map(|e| *e);
+            let solver = matches
+                .get_one::<String>("solver")
+                .and_then(|name| solver_by_name(name))
+                .expect("has a default value");
+            if live && solver.name() != "backtrack" {
+                cli()
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        format!(
+                            "--live streams backtracking solutions and can't be used with --solver {}",
+                            solver.name()
+                        ),
+                    )
+                    .exit();
+            }
+            let config = SolverConfig {
+                timeout: timeout.map(|millis| Duration::from_millis(millis as u64)),
+                threads: matches.get_one::<usize>("threads").copied(),
+                transpositions: matches.get_flag("transpositions"),
+                backtrack: matches
+                    .get_one::<BacktrackConfig>("tune")
+                    .copied()
+                    .unwrap_or_default(),
+                progress: matches
+                    .get_flag("progress")
+                    .then(|| ProgressCallback::new(print_progress)),
+                ..SolverConfig::default()
+            };
             if puzzle_ids.len() > 0 {
                 let boi: Vec<_> = if ranged {
                     get_levels((puzzle_ids[0] as u64)..=(puzzle_ids[1] as u64)).collect()
@@ -242,7 +604,9 @@ fn main() {
                                 }
                                 solutions
                             } else {
-                                let solutions = backtrack(level.puzzle, timeout);
+                                let result = solver.solve(&level.puzzle, &config);
+                                store_stats_locally(&result.stats, level.id);
+                                let solutions = result.solutions;
                                 if !solutions.is_empty() {
                                     store_solutions_locally(
                                         &solutions.iter().map(|(_, s)| *s).collect(),
@@ -251,8 +615,46 @@ fn main() {
                                 }
                                 solutions
                             }
+                        } else if let Some(objective) = objective {
+                            let result = optimal(level.puzzle, objective, &config);
+                            match result.optimality {
+                                Optimality::Proven => println!(
+                                    "Optimality: proven within {} steps",
+                                    result.step_budget
+                                ),
+                                Optimality::Bounded { lower, upper } => println!(
+                                    "Optimality: bounded within {} steps, optimum is in {}..={}",
+                                    result.step_budget,
+                                    lower,
+                                    upper.map_or("?".to_string(), |upper| upper.to_string())
+                                ),
+                            }
+                            result.best.into_iter().collect()
+                        } else if live {
+                            let mut solutions = backtrack::stream(level.puzzle, config.clone());
+                            for found in solutions.by_ref() {
+                                println!(
+                                    "found: steps: {:>2}, solution length: {:>2}, limit: {{s: {}, t: {}, i: {}}}, code: {}",
+                                    found.steps,
+                                    found.source.count_ins(),
+                                    found.limit.steps,
+                                    found.limit.touches,
+                                    found.limit.instructions,
+                                    found.source
+                                );
+                            }
+                            solutions.into_result().solutions
                         } else {
-                            backtrack(level.puzzle, timeout)
+                            let result = solver.solve(&level.puzzle, &config);
+                            if config.transpositions && !quiet {
+                                println!(
+                                    "Transpositions: {} hits, {} misses, {} cycles cut",
+                                    result.stats.transposition_hits,
+                                    result.stats.transposition_misses,
+                                    result.stats.cycles
+                                );
+                            }
+                            result.solutions
                         };
                         let el = now.elapsed();
                         if !solutions.is_empty() {
@@ -325,6 +727,31 @@ fn main() {
                 );
             }
         }
+        Some(("debug", matches)) => {
+            let puzzle_id = *matches.get_one::<i64>("puzzle ID").expect("required");
+            let level = get_level(puzzle_id as u64).expect("unable to fetch puzzle data");
+            let program = matches.get_one::<String>("program").expect("required");
+            let source = read_program(program, &level.puzzle)
+                .unwrap_or_else(|err| panic!("couldn't read the program: {}", err));
+            let title = format!("{} {}", level.id, level.title);
+            debugger::debug(&level.puzzle, source, &title).expect("terminal error");
+        }
+        Some(("verify", matches)) => {
+            let puzzle_id = *matches.get_one::<i64>("puzzle ID").expect("required");
+            let level = get_level(puzzle_id as u64).expect("unable to fetch puzzle data");
+            let program = matches.get_one::<String>("program").expect("required");
+            let report = match read_program(program, &level.puzzle) {
+                Ok(source) => verify(&level.puzzle, &source),
+                Err(err) => {
+                    eprintln!("couldn't read the program: {}", err);
+                    std::process::exit(2);
+                }
+            };
+            print!("{}", report);
+            if !report.passed() {
+                std::process::exit(1);
+            }
+        }
         Some((("misc"), matches)) => {
             println!("{:x}", 10 as usize);
             println!("{}", 10 as usize);
@@ -339,6 +766,23 @@ fn main() {
     }
 }
 
+fn print_progress(progress: &Progress) {
+    if !progress.finished {
+        return;
+    }
+    eprintln!(
+        "{{s: {:>4}, t: {:>4}, i: {:>2}}}, took {:>7} branches, s {:.3}, t {:.3}, r {:.3}, total {:>9} branches",
+        progress.steps,
+        progress.touches,
+        progress.instructions,
+        progress.branches,
+        progress.step_death_ratio(),
+        progress.touch_death_ratio(),
+        progress.death_ratio(),
+        progress.stats.branches,
+    );
+}
+
 fn print_level(level: &web::Level, long_output: bool) {
     println!(
         "Id: {:<5} | Title: {} | About: {}",
@@ -1,13 +1,196 @@
+use std::fmt::{Debug, Error, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
 use crate::game::{puzzle::Puzzle, Source};
 
-use backtrack::backtrack;
+use backtrack::{backtrack, Backtrack, BacktrackConfig};
+use backtrack_mt::ParallelBacktrack;
+use carlo::Carlo;
+use mcts::MonteCarlo;
 
 pub mod pruning;
 pub mod solutions;
 
 pub mod backtrack;
+pub mod backtrack_mt;
 pub mod carlo;
 mod mcts;
+pub mod optimal;
+
+/// Limits shared by all solving strategies, `None` means unlimited.
+#[derive(Clone, Debug, Default)]
+pub struct SolverConfig {
+    pub timeout: Option<Duration>,
+    pub instruction_cap: Option<usize>,
+    pub step_cap: Option<usize>,
+    pub max_solutions: Option<usize>,
+    /// Worker threads of parallel strategies, `None` uses every core.
+    pub threads: Option<usize>,
+    /// Whether backtracking skips frames it already explored under the
+    /// current limit and cuts off programs that loop without collecting a
+    /// star, see [`TranspositionTable`](backtrack::TranspositionTable).
+    pub transpositions: bool,
+    /// How the backtracking strategies grow their limits.
+    pub backtrack: BacktrackConfig,
+    pub cancel: CancelToken,
+    pub progress: Option<ProgressCallback>,
+}
+
+impl SolverConfig {
+    /// Whether a solution taking `steps` steps stays within the caps.
+    pub fn fits(&self, source: &Source, steps: usize) -> bool {
+        self.instruction_cap
+            .is_none_or(|cap| source.count_ins() <= cap)
+            && self.step_cap.is_none_or(|cap| steps <= cap)
+    }
+}
+
+/// Shared flag for stopping a search from another thread, clones observe the
+/// same flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>,
+    /// Cancelling the parent cancels this token, not the other way around.
+    parent: Option<Arc<CancelToken>>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// A token that is cancelled with this one but can also be cancelled on
+    /// its own.
+    pub fn child(&self) -> Self {
+        CancelToken {
+            flag: Arc::default(),
+            parent: Some(Arc::new(self.clone())),
+        }
+    }
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+            || self
+                .parent
+                .as_ref()
+                .is_some_and(|parent| parent.is_cancelled())
+    }
+}
+
+/// Snapshot of a running search. The limit fields describe the bounds the
+/// search is currently exploring, deaths count branches cut off by them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Progress {
+    pub steps: usize,
+    pub touches: usize,
+    pub instructions: usize,
+    /// Branches explored under the current limit.
+    pub branches: u64,
+    pub step_deaths: u64,
+    pub touch_deaths: u64,
+    pub both_deaths: u64,
+    /// Whether the current limit has been fully explored.
+    pub finished: bool,
+    pub solutions: usize,
+    /// Totals over the whole search so far.
+    pub stats: SolverStats,
+}
+
+impl Progress {
+    /// Counts one explored branch that executed `steps` steps.
+    pub fn record(&mut self, steps: usize, step_death: bool, touch_death: bool) {
+        self.branches += 1;
+        self.stats.branches += 1;
+        self.stats.steps += steps as u64;
+        self.step_deaths += (step_death & !touch_death) as u64;
+        self.touch_deaths += (touch_death & !step_death) as u64;
+        self.both_deaths += (step_death & touch_death) as u64;
+    }
+    pub fn step_death_ratio(&self) -> f64 {
+        self.step_deaths as f64 / self.branches.max(1) as f64
+    }
+    pub fn touch_death_ratio(&self) -> f64 {
+        self.touch_deaths as f64 / self.branches.max(1) as f64
+    }
+    pub fn death_ratio(&self) -> f64 {
+        (self.step_deaths + self.touch_deaths + self.both_deaths) as f64
+            / self.branches.max(1) as f64
+    }
+}
+
+/// Called by the solver whenever it reports [`Progress`], possibly from the
+/// solving thread.
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(&Progress) + Send + Sync>);
+
+impl ProgressCallback {
+    pub fn new(callback: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        ProgressCallback(Arc::new(callback))
+    }
+    pub fn report(&self, progress: &Progress) {
+        (self.0)(progress)
+    }
+}
+
+impl Debug for ProgressCallback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "ProgressCallback")
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SolverStats {
+    pub branches: u64,
+    pub steps: u64,
+    pub elapsed: Duration,
+    /// Frames skipped because the transposition table had explored them.
+    pub transposition_hits: u64,
+    pub transposition_misses: u64,
+    /// Branches cut off because their program looped without progress.
+    pub cycles: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// The search space allowed by the configuration was fully explored.
+    Exhausted,
+    Timeout,
+    Cancelled,
+    SolutionLimit,
+    /// The strategy stopped on its own without covering the search space.
+    GaveUp,
+}
+
+#[derive(Clone, Debug)]
+pub struct SolverResult {
+    /// Pairs of (steps, solution).
+    pub solutions: Vec<(usize, Source)>,
+    pub stats: SolverStats,
+    pub stop_reason: StopReason,
+}
+
+pub trait Solver {
+    fn name(&self) -> &'static str;
+    fn solve(&self, puzzle: &Puzzle, config: &SolverConfig) -> SolverResult;
+}
+
+pub fn solvers() -> Vec<Box<dyn Solver>> {
+    vec![
+        Box::new(Backtrack),
+        Box::new(ParallelBacktrack),
+        Box::new(Carlo::default()),
+        Box::new(MonteCarlo),
+    ]
+}
+
+pub fn solver_by_name(name: &str) -> Option<Box<dyn Solver>> {
+    solvers().into_iter().find(|solver| solver.name() == name)
+}
 
 pub fn solve(puzzle: Puzzle) -> Vec<(usize, Source)> {
     return backtrack(puzzle, None);
@@ -1,21 +1,30 @@
 use std::cmp::{max, Ordering, Reverse};
-use std::collections::{BinaryHeap, HashSet, VecDeque};
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::hash::{Hash, Hasher};
 use std::io::{stdout, Write};
+use std::str::FromStr;
 use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering as SyncOrdering};
-use std::thread::spawn;
+use std::thread::{spawn, JoinHandle};
 use std::time::{Duration, Instant};
 
 use crossbeam_channel::{unbounded, Receiver, Sender};
 
 use super::pruning::*;
+use super::{Progress, Solver, SolverConfig, SolverResult, SolverStats, StopReason};
 use crate::constants::*;
 use crate::game::instructions::*;
+use crate::game::state::{StackMatch, Termination};
 use crate::game::{puzzle::Puzzle, state::State, Source};
 use crate::web::encode_program;
 
 const BACKTRACK_STACK_SIZE: usize = 2200;
 const PHI: f64 = 1.61803398875;
+/// Branches between progress reports while a limit is being explored.
+const PROGRESS_INTERVAL: u64 = 1 << 16;
+/// Frames the [`TranspositionTable`] of one limit holds before it starts over.
+const TRANSPOSITION_CAPACITY: usize = 1 << 20;
+/// Steps between two cycle checks of a running frame.
+const CYCLE_CHECK_INTERVAL: usize = 1 << 6;
 // 44 * 50
 static MAX_INS: AtomicUsize = AtomicUsize::new(0);
 
@@ -29,7 +38,7 @@ pub struct Frame {
 }
 
 impl Frame {
-    fn new(puzzle: &Puzzle) -> Frame {
+    pub(super) fn new(puzzle: &Puzzle) -> Frame {
         Frame {
             candidate: puzzle.empty_source(),
             state: puzzle.initial_state(&NOGRAM),
@@ -40,13 +49,176 @@ impl Frame {
     }
 }
 
-#[derive(Clone, Copy, Default)]
-struct Limit {
+/// Frames explored under the current limit, keyed by the fingerprint of their
+/// state, their touch limit and their candidate. A frame reaching an explored
+/// position with the same candidate in no fewer steps can't find anything new.
+/// While touches are limited the touch counts of the tiles are part of the
+/// fingerprint, a frame that touched the tiles less may get further. Entries
+/// keep a second fingerprint of another seed, so that a collision of the
+/// first one does not cut off a frame.
+#[derive(Default)]
+pub struct TranspositionTable {
+    explored: HashMap<(u64, usize, Source), (u64, usize)>,
+    hits: u64,
+    misses: u64,
+    cycles: u64,
+}
+
+impl TranspositionTable {
+    /// Whether an equivalent frame was explored before, records `frame`
+    /// otherwise.
+    fn explored(&mut self, frame: &Frame) -> bool {
+        let touches = frame.max_touches != usize::MAX;
+        let fingerprint = |seed| {
+            frame
+                .state
+                .seeded_fingerprint(StackMatch::Exact, touches, seed)
+        };
+        let key = (fingerprint(0), frame.max_touches, frame.candidate);
+        let check = fingerprint(1);
+        match self.explored.get_mut(&key) {
+            Some(&mut (explored, steps)) if explored == check && steps <= frame.state.steps => {
+                self.hits += 1;
+                return true;
+            }
+            Some(entry) => *entry = (check, frame.state.steps),
+            None => {
+                if self.explored.len() >= TRANSPOSITION_CAPACITY {
+                    self.explored.clear();
+                }
+                self.explored.insert(key, (check, frame.state.steps));
+            }
+        }
+        self.misses += 1;
+        false
+    }
+    /// Moves the counts of the table into `stats`.
+    pub(super) fn flush(&mut self, stats: &mut SolverStats) {
+        stats.transposition_hits += self.hits;
+        stats.transposition_misses += self.misses;
+        stats.cycles += self.cycles;
+        (self.hits, self.misses, self.cycles) = (0, 0, 0);
+    }
+}
+
+/// Brent's cycle detection over the states of a running frame, looked at
+/// every [`CYCLE_CHECK_INTERVAL`] steps. A state that comes back without a
+/// star being collected loops until it runs into a limit.
+#[derive(Default)]
+struct CycleCheck {
+    checkpoint: Option<(u64, State)>,
+    since: usize,
+    window: usize,
+}
+
+impl CycleCheck {
+    /// Forgets the states seen so far, after the candidate changed.
+    fn reset(&mut self) {
+        *self = CycleCheck::default();
+    }
+    fn repeats(&mut self, state: &State) -> bool {
+        let fingerprint = state.fingerprint(StackMatch::Exact);
+        if let Some((checkpoint_fingerprint, checkpoint)) = &self.checkpoint {
+            if fingerprint == *checkpoint_fingerprint
+                && state.same_position(checkpoint, StackMatch::Exact)
+            {
+                return true;
+            }
+        }
+        self.since += 1;
+        if self.since > self.window {
+            self.checkpoint = Some((fingerprint, state.clone()));
+            self.since = 0;
+            self.window = (self.window * 2).max(1);
+        }
+        false
+    }
+}
+
+/// Parameters of the iterative deepening over [`Limit`]s. The defaults are the
+/// values the search was tuned with on the easy puzzles.
+///
+/// Parsed from a comma separated list of `name=value` pairs that override
+/// the defaults, e.g. `touch_scale=3,max_death_ratio=0.8`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BacktrackConfig {
+    /// Steps of the first limit per tile the robot could reach.
+    pub initial_steps_per_tile: usize,
+    pub initial_touches: usize,
+    pub initial_instructions: usize,
+    /// Factor the touches grow by when a limit has deaths, the cost of the
+    /// grown limit is scaled by it as well.
+    pub touch_scale: f64,
+    /// The cost of a grown limit is multiplied by this to the power of the
+    /// touches of the limit it grew from.
+    pub touch_cost_base: f64,
+    /// Limits whose branches die less often than this ratio are followed by
+    /// a limit with one more instruction.
+    pub max_death_ratio: f64,
+    /// Once a solution is found, limits with `n` fewer instructions are
+    /// capped at its steps and touches times `cap_factor * n + 1`.
+    pub cap_factor: usize,
+    /// Whether a solution caps the steps of the frames explored after it
+    /// under the same limit. Without it every solution within a limit is
+    /// found, whatever order its frames are explored in.
+    pub tighten_steps: bool,
+}
+
+impl Default for BacktrackConfig {
+    fn default() -> Self {
+        BacktrackConfig {
+            initial_steps_per_tile: 8,
+            initial_touches: 8,
+            initial_instructions: 1,
+            touch_scale: 2.0,
+            touch_cost_base: 1.3,
+            max_death_ratio: 0.9,
+            cap_factor: 4,
+            tighten_steps: true,
+        }
+    }
+}
+
+impl FromStr for BacktrackConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn parse<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+            value
+                .parse()
+                .map_err(|_| format!("invalid value for {}: {}", name, value))
+        }
+        let mut config = BacktrackConfig::default();
+        for pair in s.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let (name, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected name=value, got {}", pair))?;
+            let (name, value) = (name.trim(), value.trim());
+            match name {
+                "initial_steps_per_tile" => config.initial_steps_per_tile = parse(name, value)?,
+                "initial_touches" => config.initial_touches = parse(name, value)?,
+                "initial_instructions" => config.initial_instructions = parse(name, value)?,
+                "touch_scale" => config.touch_scale = parse(name, value)?,
+                "touch_cost_base" => config.touch_cost_base = parse(name, value)?,
+                "max_death_ratio" => config.max_death_ratio = parse(name, value)?,
+                "cap_factor" => config.cap_factor = parse(name, value)?,
+                "tighten_steps" => config.tighten_steps = parse(name, value)?,
+                _ => return Err(format!("unknown backtracking parameter: {}", name)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Bounds of one iteration of the search, solutions are only looked for
+/// within them.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Limit {
     cost: f64,
     old_steps: usize,
-    steps: usize,
-    touches: usize,
-    instructions: usize,
+    pub steps: usize,
+    pub touches: usize,
+    pub instructions: usize,
     increased: Increased,
 }
 
@@ -58,7 +230,7 @@ impl Hash for Limit {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Default)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
 enum Increased {
     #[default]
     Steps,
@@ -91,13 +263,146 @@ impl Ord for Limit {
     }
 }
 
+pub struct Backtrack;
+
+impl Solver for Backtrack {
+    fn name(&self) -> &'static str {
+        "backtrack"
+    }
+    fn solve(&self, puzzle: &Puzzle, config: &SolverConfig) -> SolverResult {
+        search_limits(*puzzle, config, |_| ())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FoundSolution {
+    pub steps: usize,
+    pub source: Source,
+    pub limit: Limit,
+}
+
+/// Iterator over the solutions of a backtracking search running on a
+/// background thread, yielding each one as soon as it is found.
+///
+/// Dropping the stream cancels the search through a
+/// [`child`](super::CancelToken::child) of the config's token, which the
+/// config's token still cancels.
+pub struct SolutionStream {
+    receiver: Receiver<FoundSolution>,
+    config: SolverConfig,
+    handle: Option<JoinHandle<SolverResult>>,
+}
+
+pub fn stream(puzzle: Puzzle, config: SolverConfig) -> SolutionStream {
+    let (sender, receiver) = unbounded();
+    // dropping the stream must not cancel other searches sharing the token
+    let config = SolverConfig {
+        cancel: config.cancel.child(),
+        ..config
+    };
+    let handle = {
+        let config = config.clone();
+        spawn(move || {
+            search_limits(puzzle, &config, |found| {
+                // the receiver only disconnects when the stream is dropped,
+                // which cancels the search anyway
+                let _ = sender.send(*found);
+            })
+        })
+    };
+    SolutionStream {
+        receiver,
+        config,
+        handle: Some(handle),
+    }
+}
+
+impl SolutionStream {
+    /// Waits for the search to end and returns its result, including the
+    /// solutions that were already yielded.
+    pub fn into_result(mut self) -> SolverResult {
+        self.handle
+            .take()
+            .expect("only taken on consumption")
+            .join()
+            .expect("search thread panicked")
+    }
+}
+
+impl Iterator for SolutionStream {
+    type Item = FoundSolution;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl Drop for SolutionStream {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.config.cancel.cancel();
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Backtracking search with an optional timeout in milliseconds.
 pub fn backtrack(puzzle: Puzzle, timeout: Option<u128>) -> Vec<(usize, Source)> {
+    let config = SolverConfig {
+        timeout: timeout.map(|millis| Duration::from_millis(millis as u64)),
+        ..SolverConfig::default()
+    };
+    search_limits(puzzle, &config, |_| ()).solutions
+}
+
+/// State shared between [`search_limits_with`] and the function exploring a
+/// single limit.
+pub(super) struct Exploration<'a> {
+    pub puzzle: &'a Puzzle,
+    pub config: &'a SolverConfig,
+    pub start: Instant,
+    /// Solutions under this limit have to take fewer steps than this.
+    pub step_cap: usize,
+    pub progress: Progress,
+    /// Steps executed while exploring this limit.
+    pub steps: usize,
+}
+
+pub(super) fn search_limits(
+    puzzle: Puzzle,
+    config: &SolverConfig,
+    on_solution: impl FnMut(&FoundSolution),
+) -> SolverResult {
+    search_limits_with(puzzle, config, on_solution, explore)
+}
+
+/// Iterative deepening over [`Limit`]s, `explore` searches all frames under
+/// one limit and calls its `solved` argument for every solution, which
+/// returns false when the search should stop.
+pub(super) fn search_limits_with(
+    puzzle: Puzzle,
+    config: &SolverConfig,
+    mut on_solution: impl FnMut(&FoundSolution),
+    mut explore: impl FnMut(
+        &mut Exploration,
+        Frame,
+        &mut dyn FnMut(&Frame) -> bool,
+    ) -> Option<StopReason>,
+) -> SolverResult {
+    let start = Instant::now();
     let instruction_set_length = puzzle.get_ins_set(INS_COLOR_MASK, true).len();
     //let mut max_instructions = puzzle.methods.iter().sum();
     let mut step_cap = [usize::MAX; 50];
     let mut checked = [0; 50];
     let mut touch_cap = [usize::MAX; 50];
-    let mut instruction_cap = puzzle.methods.iter().sum();
+    let mut instruction_cap = puzzle
+        .methods
+        .iter()
+        .sum::<usize>()
+        .min(config.instruction_cap.unwrap_or(usize::MAX));
+    let mut stats = SolverStats::default();
+    let mut stop_reason = StopReason::Exhausted;
+    let tuning = config.backtrack;
 
     let reachable_tiles = puzzle.board.count_tiles();
 
@@ -109,9 +414,9 @@ pub fn backtrack(puzzle: Puzzle, timeout: Option<u128>) -> Vec<(usize, Source)>
     priorities.push(Reverse(Limit {
         cost: 2.0,
         old_steps: 1,
-        steps: 8 * reachable_tiles,
-        touches: 8,
-        instructions: 1,
+        steps: tuning.initial_steps_per_tile * reachable_tiles,
+        touches: tuning.initial_touches,
+        instructions: tuning.initial_instructions,
         increased: Increased::Steps,
     }));
     // priorities.push(Reverse(Limit {
@@ -126,16 +431,16 @@ pub fn backtrack(puzzle: Puzzle, timeout: Option<u128>) -> Vec<(usize, Source)>
     let mut last_outer_steps = 0;
     let mut solved = false;
     let mut result: Vec<(usize, Source)> = vec![];
-    'outer: while let Some(Reverse(mut limit)) = priorities.pop() {
+    while let Some(Reverse(mut limit)) = priorities.pop() {
         limit.instructions = limit.instructions.min(instruction_cap);
         if solved {
             limit.steps = (step_cap[limit.instructions]);
             limit.touches = (touch_cap[limit.instructions]);
         }
+        limit.steps = limit.steps.min(config.step_cap.unwrap_or(usize::MAX));
         if !done_limits.insert(limit) {
             continue;
         }
-        let now = Instant::now();
         let mut outer_frame = Frame::new(&puzzle);
         outer_frame.max_steps = limit.steps;
         outer_frame.max_touches = limit.touches;
@@ -150,84 +455,97 @@ pub fn backtrack(puzzle: Puzzle, timeout: Option<u128>) -> Vec<(usize, Source)>
             stdout().flush().unwrap();
         }
 
-        let mut candidates = VecDeque::new();
-        candidates.push_back(outer_frame);
-        let mut branches: u64 = 0;
-        let mut outer_steps = 0;
-        let mut step_deaths = 0;
-        let mut touch_deaths = 0;
-        let mut both_deaths = 0;
-        while let Some(mut frame) = candidates.pop_back() {
-            frame.max_steps = frame.max_steps.min(step_cap[limit.instructions] - 1);
-
-            branches += 1;
-            let (is_solution, after_steps, step_death, touch_death) =
-                search(&puzzle, &mut frame, &mut candidates);
-            outer_steps += after_steps;
-            step_deaths += (step_death & !touch_death) as usize;
-            touch_deaths += (touch_death & !step_death) as usize;
-            both_deaths += (step_death & touch_death) as usize;
-
-            if is_solution {
-                let mut solution = frame.candidate.clone();
-                solution.sanitize();
-                let max_touches = frame.state.board.max_touches();
-                result.push((frame.state.steps, solution));
-                for incnt in 1..=limit.instructions {
-                    step_cap[incnt] = frame.state.steps * (4 * (limit.instructions - incnt) + 1);
-                    touch_cap[incnt] = max_touches * (4 * (limit.instructions - incnt) + 1);
-                }
-                instruction_cap = limit.instructions - 1;
-                solved = true;
-                if PRINT_STUFF {
-                    println!();
-                    println!(
-                        "solved! candidates: {}, current: {}, ins: {}, steps: {}, touches: {}, code: {}",
-                        candidates.len(),
-                        frame.candidate,
-                        frame.candidate.count_ins(),
-                        frame.state.steps,
-                        max_touches,
-                        encode_program(&frame.candidate, &puzzle)
-                    );
-                }
-                //candidates.clear();
-                coz::progress!("backtrack frame");
+        let mut exploration = Exploration {
+            puzzle: &puzzle,
+            config,
+            start,
+            step_cap: step_cap[limit.instructions],
+            progress: Progress {
+                steps: limit.steps,
+                touches: limit.touches,
+                instructions: limit.instructions,
+                solutions: result.len(),
+                stats,
+                ..Progress::default()
+            },
+            steps: 0,
+        };
+        // parallel explorations may report solutions out of order, only the
+        // fewest steps, then touches, under this limit tighten the caps
+        let mut limit_best = (usize::MAX, usize::MAX);
+        let interrupted = explore(&mut exploration, outer_frame, &mut |frame| {
+            let mut solution = frame.candidate.clone();
+            solution.sanitize();
+            let max_touches = frame.state.board.max_touches();
+            if !result.contains(&(frame.state.steps, solution)) {
+                on_solution(&FoundSolution {
+                    steps: frame.state.steps,
+                    source: solution,
+                    limit,
+                });
             }
-            coz::progress!("backtrack frame");
-            if let Some(timeout) = timeout {
-                if now.elapsed().as_millis() > timeout {
-                    break 'outer;
+            result.push((frame.state.steps, solution));
+            if (frame.state.steps, max_touches) < limit_best {
+                limit_best = (frame.state.steps, max_touches);
+                for incnt in 1..=limit.instructions {
+                    let factor = tuning.cap_factor * (limit.instructions - incnt) + 1;
+                    step_cap[incnt] = frame.state.steps * factor;
+                    touch_cap[incnt] = max_touches * factor;
                 }
             }
-            if true || branches % (1 << 8) == 0 || solved {
-                // println!(
-                //     "candidates: {}, current: {}, ins: {}",
-                //     candidates.len(),
-                //     frame.candidate,
-                //     frame.candidate.count_ins()
-                // );
-                // for c in candidates.iter().rev().take(10) {
-                //     println!("queued: {}, ins: {}", c.candidate, c.candidate.count_ins());
-                // }
+            instruction_cap = limit.instructions - 1;
+            solved = true;
+            if PRINT_STUFF {
+                println!();
+                println!(
+                    "solved! current: {}, ins: {}, steps: {}, touches: {}, code: {}",
+                    frame.candidate,
+                    frame.candidate.count_ins(),
+                    frame.state.steps,
+                    max_touches,
+                    encode_program(&frame.candidate, &puzzle).unwrap_or_else(|err| err.to_string())
+                );
             }
+            !config
+                .max_solutions
+                .is_some_and(|max_solutions| result.len() >= max_solutions)
+        });
+        let Exploration {
+            mut progress,
+            steps: outer_steps,
+            ..
+        } = exploration;
+        stats = progress.stats;
+        if let Some(reason) = interrupted {
+            stop_reason = reason;
+            break;
+        }
+        progress.finished = true;
+        progress.solutions = result.len();
+        progress.stats.elapsed = start.elapsed();
+        if let Some(callback) = &config.progress {
+            callback.report(&progress);
         }
-        let deaths = step_deaths + touch_deaths + both_deaths;
-        let death_ratio = deaths as f64 / branches as f64;
+        let deaths = progress.step_deaths + progress.touch_deaths + progress.both_deaths;
+        let death_ratio = progress.death_ratio();
 
         if PRINT_STUFF {
-            print!(", took {:>7} branches, {:>11} steps", branches, outer_steps);
+            print!(
+                ", took {:>7} branches, {:>11} steps",
+                progress.branches, outer_steps
+            );
             print!(
                 ", s {}, t {}, b {}, r {:.3}",
-                step_deaths, touch_deaths, both_deaths, death_ratio
+                progress.step_deaths, progress.touch_deaths, progress.both_deaths, death_ratio
             );
             println!();
         }
 
         if deaths > 0 {
-            let scale = 2.0;
+            let scale = tuning.touch_scale;
             let cost = outer_steps as f64
-                + (outer_steps as f64 * death_ratio * scale) * (1.3_f64.powf(limit.touches as f64));
+                + (outer_steps as f64 * death_ratio * scale)
+                    * (tuning.touch_cost_base.powf(limit.touches as f64));
             let next_touches = (limit.touches as f64 * scale).ceil() as usize;
             let next_steps = limit.touches * (reachable_tiles);
             let limit = Limit {
@@ -244,7 +562,7 @@ pub fn backtrack(puzzle: Puzzle, timeout: Option<u128>) -> Vec<(usize, Source)>
             // );
             priorities.push(Reverse(limit));
         }
-        if death_ratio < 0.9 {
+        if death_ratio < tuning.max_death_ratio {
             let cost = outer_steps as f64 * (instruction_set_length as f64 / 2.0);
             let limit = Limit {
                 cost,
@@ -263,14 +581,95 @@ pub fn backtrack(puzzle: Puzzle, timeout: Option<u128>) -> Vec<(usize, Source)>
 
     result.sort();
     result.dedup();
-    return result;
+    stats.elapsed = start.elapsed();
+    SolverResult {
+        solutions: result,
+        stats,
+        stop_reason,
+    }
 }
 
-fn search(
+/// Depth first search of all frames under one limit on the current thread.
+pub(super) fn explore(
+    exploration: &mut Exploration,
+    outer_frame: Frame,
+    solved: &mut dyn FnMut(&Frame) -> bool,
+) -> Option<StopReason> {
+    let (puzzle, config) = (exploration.puzzle, exploration.config);
+    let mut candidates = VecDeque::new();
+    let mut table = config.transpositions.then(TranspositionTable::default);
+    candidates.push_back(outer_frame);
+    while let Some(mut frame) = candidates.pop_back() {
+        frame.max_steps = frame.max_steps.min(exploration.step_cap - 1);
+
+        let (is_solution, after_steps, step_death, touch_death) =
+            search(puzzle, &mut frame, &mut candidates, table.as_mut());
+        if let Some(table) = &mut table {
+            table.flush(&mut exploration.progress.stats);
+        }
+        exploration.steps += after_steps;
+        exploration
+            .progress
+            .record(after_steps, step_death, touch_death);
+
+        if is_solution {
+            if config.backtrack.tighten_steps {
+                exploration.step_cap = frame.state.steps;
+            }
+            exploration.progress.solutions += 1;
+            //candidates.clear();
+            coz::progress!("backtrack frame");
+            if !solved(&frame) {
+                return Some(StopReason::SolutionLimit);
+            }
+        }
+        coz::progress!("backtrack frame");
+        if let Some(reason) = interruption(config, exploration.start) {
+            return Some(reason);
+        }
+        if let Some(callback) = &config.progress {
+            if exploration
+                .progress
+                .branches
+                .is_multiple_of(PROGRESS_INTERVAL)
+            {
+                exploration.progress.stats.elapsed = exploration.start.elapsed();
+                callback.report(&exploration.progress);
+            }
+        }
+    }
+    None
+}
+
+/// Why the search has to stop regardless of its progress, if it has to.
+pub(super) fn interruption(config: &SolverConfig, start: Instant) -> Option<StopReason> {
+    if config.cancel.is_cancelled() {
+        Some(StopReason::Cancelled)
+    } else if config
+        .timeout
+        .is_some_and(|timeout| start.elapsed() > timeout)
+    {
+        Some(StopReason::Timeout)
+    } else {
+        None
+    }
+}
+
+/// Runs `frame` until it wins or dies, queueing a frame for every other
+/// instruction the holes it runs into could be filled with. Returns whether
+/// it won, the steps it took and whether it ran into the step and the touch
+/// limit. A frame cut off because it loops counts as running into the step
+/// limit.
+pub(super) fn search(
     puzzle: &Puzzle,
     mut frame: &mut Frame,
     mut candidates: &mut VecDeque<Frame>,
+    mut table: Option<&mut TranspositionTable>,
 ) -> (bool, usize, bool, bool) {
+    if table.as_mut().is_some_and(|table| table.explored(frame)) {
+        return (false, 0, false, false);
+    }
+    let mut cycle = table.is_some().then(CycleCheck::default);
     let mut preferred = [true; 5];
     for i in 1..frame.candidate.0.len() {
         for j in (i + 1)..frame.candidate.0.len() {
@@ -334,18 +733,42 @@ fn search(
             }
 
             frame.candidate[method_index][ins_index] = *replacement_instruction;
+            if let Some(cycle) = &mut cycle {
+                cycle.reset();
+            }
             //break;
         }
 
         coz::progress!("search state step");
         // running = frame.state.step(&frame.candidate, puzzle);
+        let budget = frame.max_steps - frame.state.steps;
+        let chunk = match cycle {
+            Some(_) => budget.min(CYCLE_CHECK_INTERVAL),
+            None => budget,
+        };
         running = frame.state.steps(
             &frame.candidate,
             puzzle,
-            frame.max_steps - frame.state.steps,
+            chunk,
             // 1,
             frame.max_touches,
         );
+        // only a frame that ran through its chunk can be looping, the others
+        // died or stopped at a hole
+        if chunk < budget && frame.state.termination() == Some(Termination::StepLimit) {
+            frame.state.termination = None;
+            running = true;
+            if cycle
+                .as_mut()
+                .is_some_and(|cycle| cycle.repeats(&frame.state))
+            {
+                if let Some(table) = table {
+                    table.cycles += 1;
+                }
+                // the loop would have run into the step limit
+                return (false, frame.state.steps - pre_steps, true, false);
+            }
+        }
     }
     return (
         frame.state.stars == 0,
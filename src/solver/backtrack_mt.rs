@@ -1,292 +1,229 @@
-const THREADS: usize = 32;
+use std::collections::VecDeque;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::thread::{available_parallelism, scope};
+use std::time::{Duration, Instant};
 
-pub fn obacktrack(puzzle: Puzzle) -> Vec<(usize, Source)> {
-    //    let mut tested: HashSet<u64, _> = HashSet::new();
-    //    for thread in 0..THREADS {
-    //        thread::spawn(move || {
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, Sender};
 
-    let (sender, receiver) = unbounded();
-    //    for i in 2..puzzle.functions.iter().sum() {
-    //    sender.send();
-    //    }
-    MAX_INS.store(puzzle.methods.iter().sum(), SyncOrdering::Relaxed);
-    MAX_SCORE.store(16, SyncOrdering::Relaxed);
-    MAX_INS.store(9, SyncOrdering::Relaxed);
+use super::backtrack::{
+    interruption, search, search_limits_with, Exploration, Frame, TranspositionTable,
+};
+use super::{Progress, Solver, SolverConfig, SolverResult, StopReason};
+use crate::game::puzzle::Puzzle;
 
-    //    sender.send(Frame::new(&puzzle));
-    let mut threads = vec![];
-    for thread in 0..THREADS {
-        let (sclone, rclone) = (sender.clone(), receiver.clone());
-        threads.push(spawn(move || {
-            return obacktrack_thread(&puzzle, thread, sclone, rclone);
-        }));
+/// Branches a worker explores before publishing its counts.
+const FLUSH_INTERVAL: u64 = 1 << 10;
+/// How often progress is reported while a limit is being explored.
+const REPORT_INTERVAL: Duration = Duration::from_millis(250);
+/// How long an idle worker waits for shared work before checking whether
+/// the exploration is over.
+const IDLE_WAIT: Duration = Duration::from_millis(1);
+
+/// The iterative deepening search of [`Backtrack`](super::backtrack::Backtrack)
+/// with the frames of every limit explored by a pool of worker threads.
+pub struct ParallelBacktrack;
+
+impl Solver for ParallelBacktrack {
+    fn name(&self) -> &'static str {
+        "backtrack-mt"
     }
-    let mut seeds = VecDeque::new();
-    //    for i in 2..=puzzle.methods.iter().sum() {
-    //seeds.push_front(Frame::new(&puzzle));
-    for i in 0..50 {
-        let mut f = Frame::new(&puzzle);
-        //f.max_score = fibonacci(i);
-        f.max_score = 1 << i;
-        seeds.push_front(f);
+    fn solve(&self, puzzle: &Puzzle, config: &SolverConfig) -> SolverResult {
+        backtrack_mt(*puzzle, config)
     }
-    //    }
-    while let Some(branch) = seeds.pop_back() {
-        let solved = osearch(&puzzle, branch, |branch, _| {
-            if branch.candidate.count_ins() >= 2 {
-                sender.send(branch);
-            } else {
-                seeds.push_back(branch);
-            }
-        });
+}
+
+pub fn backtrack_mt(puzzle: Puzzle, config: &SolverConfig) -> SolverResult {
+    let threads = config
+        .threads
+        .unwrap_or_else(|| available_parallelism().map(NonZeroUsize::get).unwrap_or(1));
+    search_limits_with(
+        puzzle,
+        config,
+        |_| (),
+        |exploration, frame, solved| explore(exploration, frame, solved, threads.max(1)),
+    )
+}
+
+/// Counts of the workers exploring one limit, see [`Progress`].
+#[derive(Default)]
+struct Counters {
+    branches: AtomicU64,
+    steps: AtomicU64,
+    step_deaths: AtomicU64,
+    touch_deaths: AtomicU64,
+    both_deaths: AtomicU64,
+    transposition_hits: AtomicU64,
+    transposition_misses: AtomicU64,
+    cycles: AtomicU64,
+}
+
+impl Counters {
+    /// Moves the counts of `local` into the shared counters.
+    fn flush(&self, local: &mut Progress) {
+        self.branches.fetch_add(local.branches, Ordering::Relaxed);
+        self.steps.fetch_add(local.stats.steps, Ordering::Relaxed);
+        self.step_deaths
+            .fetch_add(local.step_deaths, Ordering::Relaxed);
+        self.touch_deaths
+            .fetch_add(local.touch_deaths, Ordering::Relaxed);
+        self.both_deaths
+            .fetch_add(local.both_deaths, Ordering::Relaxed);
+        self.transposition_hits
+            .fetch_add(local.stats.transposition_hits, Ordering::Relaxed);
+        self.transposition_misses
+            .fetch_add(local.stats.transposition_misses, Ordering::Relaxed);
+        self.cycles.fetch_add(local.stats.cycles, Ordering::Relaxed);
+        *local = Progress::default();
     }
-    //    sleep(Duration::from_secs(10));
-    //    drop(sender);
-    //    drop(receiver);
-    let mut result = vec![];
-    for handle in threads {
-        match handle.join() {
-            Ok(solutions) => {
-                result.extend(solutions);
-            }
-            Err(error) => println!("Thread joining error: {:?}", error),
+    /// `base` with the shared counts added.
+    fn add_to(&self, base: &Progress) -> Progress {
+        let branches = self.branches.load(Ordering::Relaxed);
+        let mut progress = Progress {
+            branches: base.branches + branches,
+            step_deaths: base.step_deaths + self.step_deaths.load(Ordering::Relaxed),
+            touch_deaths: base.touch_deaths + self.touch_deaths.load(Ordering::Relaxed),
+            both_deaths: base.both_deaths + self.both_deaths.load(Ordering::Relaxed),
+            ..*base
         };
+        progress.stats.branches += branches;
+        progress.stats.steps += self.steps.load(Ordering::Relaxed);
+        progress.stats.transposition_hits += self.transposition_hits.load(Ordering::Relaxed);
+        progress.stats.transposition_misses += self.transposition_misses.load(Ordering::Relaxed);
+        progress.stats.cycles += self.cycles.load(Ordering::Relaxed);
+        progress
     }
-    result.sort();
-    result.dedup();
-    return result;
 }
 
-fn obacktrack_thread(
-    puzzle: &Puzzle,
-    thread_id: usize,
-    sender: Sender<Frame>,
-    receiver: Receiver<Frame>,
-) -> Vec<(usize, Source)> {
-    let mut result: Vec<(usize, Source)> = vec![];
-    let mut candidates = VecDeque::new();
-    let mut considered: u64 = 0;
-    while let Ok(outer_frame) = receiver.recv_timeout(Duration::from_millis(100)) {
-        if outer_frame.max_score > MAX_SCORE.load(SyncOrdering::Relaxed) {
-            continue;
+/// State the workers of one limit share.
+struct Shared<'a> {
+    puzzle: &'a Puzzle,
+    config: &'a SolverConfig,
+    start: Instant,
+    /// Frames in the work queue plus workers that are busy exploring.
+    outstanding: AtomicUsize,
+    step_cap: AtomicUsize,
+    stop: AtomicBool,
+    counters: Counters,
+    threads: usize,
+}
+
+fn explore(
+    exploration: &mut Exploration,
+    outer_frame: Frame,
+    solved: &mut dyn FnMut(&Frame) -> bool,
+    threads: usize,
+) -> Option<StopReason> {
+    let shared = Shared {
+        puzzle: exploration.puzzle,
+        config: exploration.config,
+        start: exploration.start,
+        outstanding: AtomicUsize::new(1),
+        step_cap: AtomicUsize::new(exploration.step_cap),
+        stop: AtomicBool::new(false),
+        counters: Counters::default(),
+        threads,
+    };
+    let (work_sender, work_receiver) = unbounded();
+    let (solution_sender, solution_receiver) = unbounded();
+    work_sender.send(outer_frame).expect("receiver is alive");
+    let mut stop_reason = None;
+    scope(|scope| {
+        for _ in 0..threads {
+            let (work_sender, work_receiver) = (work_sender.clone(), work_receiver.clone());
+            let solution_sender = solution_sender.clone();
+            let shared = &shared;
+            scope.spawn(move || worker(shared, work_sender, work_receiver, solution_sender));
         }
-        candidates.push_back(outer_frame);
-        while let Some(frame) = candidates.pop_back() {
-            let candidate = frame.candidate;
-            let score = frame.score;
-            let max_score = frame.max_score;
-            let steps = frame.state.steps;
-            considered += 1;
-            if candidate.count_ins() > MAX_INS.load(SyncOrdering::Relaxed) {
-                continue;
-            }
-            let solved = osearch(puzzle, frame, |mut branch, is_nop_branch| {
-                // This is executed at the heart of the search function
-                if !is_nop_branch
-                    || branch.candidate.count_ins() <= MAX_INS.load(SyncOrdering::Relaxed)
-                {
-                    if branch.score > branch.max_score
-                    //&& receiver.len() < (1 << 20)
-                    {
-                        let new_max =
-                            max(branch.max_score * 2, MAX_SCORE.load(SyncOrdering::Relaxed));
-                        MAX_SCORE.store(new_max, SyncOrdering::Relaxed);
-                    //sender.send(Frame { ..branch });
-                    } else if branch.candidate.count_ins() <= MAX_INS.load(SyncOrdering::Relaxed) {
-                        // let preshade = candidate.clone();
-                        branch.candidate.shade(MAX_INS.load(SyncOrdering::Relaxed));
-                        // if (preshade.get_hash() != candidate.get_hash()) {
-                        //     println!("before shade: {}", preshade);
-                        //     println!(" after shade: {}", candidate);
-                        // }
-                        //if !deny(&puzzle, &branch.candidate, false) {
-                        candidates.push_back(Frame { ..branch });
-                        //}
+        drop(solution_sender);
+        // solutions are handled here so that `solved` never leaves this thread
+        loop {
+            match solution_receiver.recv_timeout(REPORT_INTERVAL) {
+                Ok(frame) => {
+                    exploration.progress.solutions += 1;
+                    if stop_reason.is_none() && !solved(&frame) {
+                        stop_reason = Some(StopReason::SolutionLimit);
+                        shared.stop.store(true, Ordering::Relaxed);
                     }
                 }
-            });
-
-            if considered % (1 << 14) == 0 {
-                if receiver.is_empty() || receiver.len() > 1 << 24 {
-                    drop(sender);
-                    return result;
-                }
-            }
-            if considered % (1 << 20) == 0 || solved {
-                //                if state.stars == 0 { print!("solution: "); }
-                //                println!("considered: {}", considered);
-                println!(
-                    "work queue: {}, score: {}, MAX_INS: {}, MAX_SCORE: {}, steps: {}",
-                    sender.len(),
-                    score,
-                    MAX_INS.load(SyncOrdering::Relaxed),
-                    max_score,
-                    steps
-                );
-                println!("candidates: {}, current: {}", candidates.len(), candidate);
-                // for c in candidates.iter().rev().take(32) {
-                //     println!("{}", c.candidate);
-                // }
-                //                puzzle.execute(&candidate, true, won);
-                //                print!(" and {}", candidates);
-                if solved {
-                    let mut solution = candidate.clone();
-                    solution.sanitize();
-                    result.push((steps, solution));
-                    MAX_INS.store(solution.count_ins() - 0, SyncOrdering::Relaxed);
-
-                    println!("Solution found! {}", solution);
-                    println!("code: {}", encode_program(&solution, puzzle));
-                    println!("Candidate was {}", &candidate);
-                    //while let Ok(dumped) = receiver.recv_timeout(Duration::from_millis(1)) {}
-                    //drop(sender);
-                    //return result;
-                    //                println!("candidates length is {}", candidates.len());
-                    //                break;
+                Err(RecvTimeoutError::Timeout) => {
+                    if let Some(callback) = &shared.config.progress {
+                        let mut progress = shared.counters.add_to(&exploration.progress);
+                        progress.stats.elapsed = exploration.start.elapsed();
+                        callback.report(&progress);
+                    }
                 }
+                Err(RecvTimeoutError::Disconnected) => break,
             }
         }
+    });
+    exploration.progress = shared.counters.add_to(&exploration.progress);
+    exploration.steps += shared.counters.steps.load(Ordering::Relaxed) as usize;
+    exploration.step_cap = shared.step_cap.load(Ordering::Relaxed);
+    if stop_reason.is_none() && shared.stop.load(Ordering::Relaxed) {
+        stop_reason = interruption(shared.config, shared.start);
     }
-    return result;
+    stop_reason
 }
 
-fn osearch<F>(
-    puzzle: &Puzzle,
-    Frame {
-        mut candidate,
-        mut state,
-        score,
-        max_score,
-    }: Frame,
-    mut brancher: F,
-) -> bool
-where
-    F: FnMut(Frame, bool),
-{
-    //    candidate.shade(MAX_INS.load(SyncOrdering::Relaxed));
-    //    if deny(puzzle, &candidate, false) { return false; }
-    let mut preferred = [true; 5];
-    for i in 1..candidate.0.len() {
-        for j in (i + 1)..candidate.0.len() {
-            if candidate.0[i] == candidate.0[j] {
-                preferred[j] = false;
+fn worker(
+    shared: &Shared,
+    work_sender: Sender<Frame>,
+    work_receiver: Receiver<Frame>,
+    solutions: Sender<Frame>,
+) {
+    let mut local = Progress::default();
+    let mut candidates = VecDeque::new();
+    // each worker skips the frames it explored itself
+    let mut table = shared
+        .config
+        .transpositions
+        .then(TranspositionTable::default);
+    while !shared.stop.load(Ordering::Relaxed) {
+        let frame = match work_receiver.recv_timeout(IDLE_WAIT) {
+            Ok(frame) => frame,
+            Err(_) if shared.outstanding.load(Ordering::SeqCst) == 0 => break,
+            Err(_) => continue,
+        };
+        candidates.push_back(frame);
+        while let Some(mut frame) = candidates.pop_back() {
+            frame.max_steps = frame
+                .max_steps
+                .min(shared.step_cap.load(Ordering::Relaxed) - 1);
+
+            let (is_solution, after_steps, step_death, touch_death) =
+                search(shared.puzzle, &mut frame, &mut candidates, table.as_mut());
+            if let Some(table) = &mut table {
+                table.flush(&mut local.stats);
             }
-        }
-    }
-    let mut branched = false;
-    let mut running = true;
-    while running {
-        if !branched {
-            let ins_pointer = state.ins_pointer();
-            let ins = state.current_ins(&candidate);
-            let method_index = ins_pointer.get_method_index();
-            let ins_index = ins_pointer.get_ins_index();
-            let nop_branch = ins.is_nop();
-            let probe_branch = ins.is_probe() && state.current_tile().clone().executes(ins);
-            let loosening_branch = !ins.is_debug()
-                && !ins.is_loosened()
-                && !state.current_tile().to_condition().is_cond(ins.get_cond());
-            if nop_branch || probe_branch || loosening_branch {
-                // instructions for branches of current program
-                let mut instructions: Vec<Ins> = if nop_branch {
-                    // Noop (unallocated) instruction hit, branches are all puzzle-legal
-                    // commands of the color of the current tile and a probe instruction.
-                    [
-                    // HALT // including halt is questionable
-                    ]
-                    .iter()
-                    .chain(
-                        puzzle
-                            .get_ins_set(state.current_tile().to_condition(), false)
-                            .iter()
-                            .filter(|&ins| !ins.is_function() || preferred[ins.source_index()]),
-                    )
-                    .chain(
-                        puzzle
-                            .get_cond_mask()
-                            .get_probes(state.current_tile().to_condition())
-                            .iter(),
-                    )
-                    .cloned()
-                    .collect()
-                } else if probe_branch {
-                    // probe instruction hit(a nop with a color),
-                    // add a branch for each command of the current tile.
-                    puzzle
-                        .get_ins_set(state.current_tile().to_condition(), false)
-                        .iter()
-                        .map(|i| i.as_loosened())
-                        .chain(
-                            if candidate[method_index][ins_index]
-                                .remove_cond(state.current_tile().to_condition())
-                                .is_probe()
-                            {
-                                vec![candidate[method_index][ins_index]
-                                    .remove_cond(state.current_tile().to_condition())]
-                            } else {
-                                vec![]
-                            },
-                        )
-                        .collect()
-                } else if loosening_branch {
-                    // try to make current instruction gray.
-                    vec![ins.as_loosened(), ins.get_ins().as_loosened()]
-                } else {
-                    vec![]
-                };
-                //let mut rng = thread_rng();
-                //instructions.shuffle(&mut rng);
-                branched = true;
-                if nop_branch {
-                    //                    if candidate.count_ins() > MAX_INS {
-                    //                        return state.stars == 0;
-                    //                    }
-                } else if probe_branch {
-                    //                    candidate[method_index][ins_index].remove_cond(state.current_tile().to_condition());
-                    //                    if !candidate[method_index][ins_index].is_gray() {
-                    //                        branched = false;
-                    //                    }
-                }
-                for &instruction in instructions.iter().rev() {
-                    let mut temp = candidate.clone();
-                    temp[method_index][ins_index] = instruction;
-                    if !snip_around(puzzle, &temp, *ins_pointer, false)
-                        && !deny(puzzle, &candidate, false)
-                    {
-                        let branch = Frame {
-                            candidate: temp.to_owned(),
-                            state: state.clone(),
-                            score: state.map.0.iter().fold(0, |acc, &x| {
-                                acc + x.iter().fold(0, |ac, &y| -> i64 {
-                                    ac + {
-                                        let ts: i64 =
-                                            std::convert::TryInto::try_into(y.touches()).unwrap();
-                                        ts * ts - 2 * ts
-                                    } as i64
-                                })
-                            }) + state.steps as i64
-                                + 4 * (state.stars as i64 - puzzle.stars as i64),
-                            max_score,
-                        };
-                        brancher(branch, nop_branch);
-                    }
-                }
-                //                return state.stars == 0;
-                if nop_branch {
-                    // this reduces performance but is necessary to find shorter solutions.
-                    //                    let left = MAX_INS
-                    //                    for i in ins_index..puzzle.methods[method_index] {
-                    //                        candidate[method_index][i] = HALT;
-                    //                    }
-                    //                    branched = false;
-                } else if loosening_branch {
-                    //                    candidate[method_index][ins_index] = candidate[method_index][ins_index].as_loosened();
-                    //                    branched = false;
+            local.record(after_steps, step_death, touch_death);
+
+            if is_solution {
+                if shared.config.backtrack.tighten_steps {
+                    shared
+                        .step_cap
+                        .fetch_min(frame.state.steps, Ordering::Relaxed);
                 }
-                return state.stars == 0;
+                let _ = solutions.send(frame);
+            }
+            if local.branches.is_multiple_of(FLUSH_INTERVAL) {
+                shared.counters.flush(&mut local);
+            }
+            if shared.stop.load(Ordering::Relaxed)
+                || interruption(shared.config, shared.start).is_some()
+            {
+                shared.stop.store(true, Ordering::Relaxed);
+                candidates.clear();
+                break;
+            }
+            // the oldest frames are the closest to the root, so they carry
+            // the most work for idle workers
+            if candidates.len() > 1 && work_receiver.len() < shared.threads {
+                shared.outstanding.fetch_add(1, Ordering::SeqCst);
+                let _ = work_sender.send(candidates.pop_front().expect("has frames"));
             }
         }
-        running = state.step(&candidate, puzzle);
+        shared.outstanding.fetch_sub(1, Ordering::SeqCst);
     }
-    return state.stars == 0;
+    shared.counters.flush(&mut local);
 }
@@ -6,8 +6,10 @@ use std::fmt::{Display, Error, Formatter};
 
 use crate::constants::{NOGRAM, _N};
 use crate::game::puzzle::Puzzle;
-use crate::game::state::State;
+use crate::game::state::{self, won, State};
 use crate::game::{instructions::*, *};
+use crate::solver::backtrack::interruption;
+use crate::solver::{Progress, Solver, SolverConfig, SolverResult, SolverStats, StopReason};
 
 #[derive(Clone, PartialEq, PartialOrd, Debug)]
 pub struct Leaf {
@@ -102,7 +104,67 @@ impl Leaf {
     }
 }
 
-pub fn carlo(puzzle: &Puzzle, max_iters: i32, expansions: i32) -> Option<Source> {
+pub struct Carlo {
+    pub max_iters: i32,
+    pub expansions: i32,
+}
+
+impl Default for Carlo {
+    fn default() -> Self {
+        Carlo {
+            max_iters: 1 << 12,
+            expansions: 1 << 8,
+        }
+    }
+}
+
+impl Solver for Carlo {
+    fn name(&self) -> &'static str {
+        "carlo"
+    }
+    fn solve(&self, puzzle: &Puzzle, config: &SolverConfig) -> SolverResult {
+        let start = std::time::Instant::now();
+        let (solution, stop_reason) = if config.max_solutions == Some(0) {
+            (None, StopReason::SolutionLimit)
+        } else {
+            carlo(puzzle, self.max_iters, self.expansions, config, start)
+        };
+        let solutions: Vec<_> = solution
+            .filter(|source| puzzle.execute(source, false, won))
+            .map(|source| (puzzle.execute(&source, false, state::steps), source))
+            .filter(|(steps, source)| config.fits(source, *steps))
+            .into_iter()
+            .collect();
+        SolverResult {
+            // the best stem of a winning program does not always win itself
+            stop_reason: if solution.is_some() && solutions.is_empty() {
+                StopReason::GaveUp
+            } else {
+                stop_reason
+            },
+            solutions,
+            stats: SolverStats {
+                elapsed: start.elapsed(),
+                ..SolverStats::default()
+            },
+        }
+    }
+}
+
+/// Samples random programs until one wins within the caps of `config`, all
+/// expansions are spent or the search is interrupted. Progress is reported
+/// after every expansion.
+pub fn carlo(
+    puzzle: &Puzzle,
+    max_iters: i32,
+    expansions: i32,
+    config: &SolverConfig,
+    start: std::time::Instant,
+) -> (Option<Source>, StopReason) {
+    // ------------------------------------------------------------
+    const PRINT_STUFF: bool = false;
+    // ------------------------------------------------------------
+    let fits = |source: &Source| config.fits(source, puzzle.execute(source, false, state::steps));
     let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(1337);
     let instruction_set = puzzle.get_ins_set(INS_COLOR_MASK, true);
     let mut stems: Vec<Leaf> = vec![Leaf {
@@ -114,8 +176,15 @@ pub fn carlo(puzzle: &Puzzle, max_iters: i32, expansions: i32) -> Option<Source>
         ..Leaf::default()
     };
     let mut bestsource = Leaf::default();
+    let mut rollouts: u64 = 0;
     for _expansion in 0..expansions {
-        let leaf_to_branch = stems.first().unwrap().clone();
+        if let Some(reason) = interruption(config, start) {
+            return (None, reason);
+        }
+        // no stem left to sample from
+        let Some(leaf_to_branch) = stems.first().cloned() else {
+            return (None, StopReason::GaveUp);
+        };
         branches(&mut stems, puzzle, &instruction_set, &leaf_to_branch);
         let correction: f64 = F64_PREC + stems.iter().map(|l| l.uncorrected_chance()).sum::<f64>();
         //        println!("correction: {}", correction);
@@ -123,7 +192,6 @@ pub fn carlo(puzzle: &Puzzle, max_iters: i32, expansions: i32) -> Option<Source>
             panic!("correction = 0!");
         }
         //        println!("stems: {}", stems.len());
-        let mut _counter = 0;
         let mut newbest = false;
         for stem in &mut stems {
             stem.correction = correction;
@@ -132,10 +200,13 @@ pub fn carlo(puzzle: &Puzzle, max_iters: i32, expansions: i32) -> Option<Source>
                 0..(bonus + rng.gen_range(-0.5..0.5) + max_iters as f64 * stem.chance()).round()
                     as usize
             {
-                _counter += 1;
+                if let Some(reason) = interruption(config, start) {
+                    return (None, reason);
+                }
+                rollouts += 1;
                 let fullgram = random_program(puzzle, &stem.source, &instruction_set, &mut rng);
                 let newscore = puzzle.execute(&fullgram, false, score);
-                if newscore > bestboi.accumulator {
+                if newscore > bestboi.accumulator && (newscore < 1.0 || fits(&fullgram)) {
                     bestsource = stem.clone();
                     bestboi = Leaf {
                         source: fullgram,
@@ -145,7 +216,7 @@ pub fn carlo(puzzle: &Puzzle, max_iters: i32, expansions: i32) -> Option<Source>
                     };
                     newbest = true;
                     if newscore >= 1.0 {
-                        return Some(bestsource.source);
+                        return (Some(bestsource.source), StopReason::SolutionLimit);
                     }
                 }
                 stem.push(newscore);
@@ -167,20 +238,38 @@ pub fn carlo(puzzle: &Puzzle, max_iters: i32, expansions: i32) -> Option<Source>
         });
         //        println!("counter: {}", counter);
         stems.sort_unstable_by(|a, b| b.chance().partial_cmp(&a.chance()).unwrap_or(Equal));
-        let lastboi = stems.first().unwrap();
-        println!(" length: {}, lastboi: {}", stems.len(), lastboi);
+        if let Some(callback) = &config.progress {
+            callback.report(&Progress {
+                branches: rollouts,
+                stats: SolverStats {
+                    branches: rollouts,
+                    elapsed: start.elapsed(),
+                    ..SolverStats::default()
+                },
+                ..Progress::default()
+            });
+        }
+        if PRINT_STUFF {
+            if let Some(lastboi) = stems.first() {
+                println!(" length: {}, lastboi: {}", stems.len(), lastboi);
+            }
+        }
     }
-    for souce in &stems {
-        println!(" {}", souce);
+    if PRINT_STUFF {
+        for souce in &stems {
+            println!(" {}", souce);
+        }
+        print!("bestboi: ");
+        print!(" {}\nbestsource: ", bestboi);
+        println!(" {}", bestsource);
     }
-    print!("bestboi: ");
-    print!(" {}\nbestsource: ", bestboi);
-    println!(" {}", bestsource);
-    return None;
+    return (None, StopReason::GaveUp);
 }
 
 pub fn branches(tree: &mut Vec<Leaf>, puzzle: &Puzzle, instruction_set: &Vec<Ins>, leaf: &Leaf) {
-    panic!("tree.remove_item(leaf); was removed and needs to be replaced here!");
+    if let Some(index) = tree.iter().position(|stem| stem == leaf) {
+        tree.remove(index);
+    }
     let mut branch_factor = 0.0;
     for i in 0..puzzle.methods.len() {
         for j in 0..puzzle.methods[i] {
@@ -237,33 +326,27 @@ pub fn score_cmp(state: &State, puzzle: &Puzzle) -> usize {
     let mut touched = 0;
     let mut stars = 0;
     let mut tiles = 1;
-    for y in 1..13 {
-        for x in 1..17 {
-            tiles += (state.board.map.0[y][x] != _N) as usize;
-            touched += state.board.map.0[y][x].touches() as usize;
-            stars += state.board.map.0[y][x].has_star() as usize;
-        }
+    for (x, y) in Map::<MAP_WIDTH, MAP_HEIGHT>::coordinates() {
+        tiles += (state.board.map.0[y][x] != _N) as usize;
+        touched += state.board.map.0[y][x].touches() as usize;
+        stars += state.board.map.0[y][x].has_star() as usize;
     }
     return (puzzle.stars - stars) * tiles * (MAX_STEPS + 1)
         + touched * (MAX_STEPS + 1)
-        + MAX_STEPS
-        - state.steps;
+        + MAX_STEPS.saturating_sub(state.steps);
 }
 pub fn score(state: &State, puzzle: &Puzzle) -> f64 {
     let mut touched = 0;
     let mut stars = 0;
     let mut tiles = 1;
-    for y in 1..13 {
-        for x in 1..17 {
-            tiles += (state.board.map.0[y][x] != _N) as usize;
-            touched += (state.board.map.0[y][x].touches() > 0) as usize;
-            stars += state.board.map.0[y][x].has_star() as usize;
-        }
+    for (x, y) in Map::<MAP_WIDTH, MAP_HEIGHT>::coordinates() {
+        tiles += (state.board.map.0[y][x] != _N) as usize;
+        touched += (state.board.map.0[y][x].touches() > 0) as usize;
+        stars += state.board.map.0[y][x].has_star() as usize;
     }
     return (((puzzle.stars - stars) * tiles * (MAX_STEPS + 1)
         + touched * (MAX_STEPS + 1)
-        + MAX_STEPS
-        - state.steps) as f64)
+        + MAX_STEPS.saturating_sub(state.steps)) as f64)
         / ((puzzle.stars * tiles * (MAX_STEPS + 1)) as f64);
 }
 
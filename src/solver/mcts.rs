@@ -1,13 +1,18 @@
 use crate::constants::NOGRAM;
+use crate::game::state::{self, won, VmLimits};
 use crate::game::{instructions::*, puzzle::Puzzle, Source};
+use crate::solver::backtrack::interruption;
 use crate::solver::carlo::score;
 use crate::solver::pruning::{deny, snip_around};
+use crate::solver::{Progress, Solver, SolverConfig, SolverResult, SolverStats, StopReason};
 use std::f64::consts::SQRT_2;
 use std::f64::INFINITY;
 use std::fmt::{Display, Error, Formatter};
 use std::intrinsics::{logf64, sqrtf64};
+use std::time::Instant;
 
 const EXPLORATION: f64 = SQRT_2;
+const PROGRESS_INTERVAL: u64 = 1 << 20;
 
 struct Node {
     pub source: Source,
@@ -37,8 +42,8 @@ impl Node {
                     * unsafe { sqrtf64(logf64(parent_visits as f64) / self.rollouts as f64) }
         };
     }
-    fn have_children(&mut self, puzzle: &Puzzle) -> bool {
-        let mut state = puzzle.initial_state(&self.source);
+    fn have_children(&mut self, puzzle: &Puzzle, limits: VmLimits) -> bool {
+        let mut state = puzzle.initial_state(&self.source).with_limits(limits);
         let mut preferred = [true; 5];
         for i in 1..self.source.0.len() {
             for j in (i + 1)..self.source.0.len() {
@@ -122,20 +127,72 @@ impl Node {
     }
 }
 
-pub fn monte_carlo(puzzle: &Puzzle) -> Vec<Source> {
+pub struct MonteCarlo;
+
+impl Solver for MonteCarlo {
+    fn name(&self) -> &'static str {
+        "mcts"
+    }
+    fn solve(&self, puzzle: &Puzzle, config: &SolverConfig) -> SolverResult {
+        let start = Instant::now();
+        let (solutions, rollouts, stop_reason) = if config.max_solutions == Some(0) {
+            (vec![], 0, StopReason::SolutionLimit)
+        } else {
+            monte_carlo(puzzle, config, start)
+        };
+        let sampled = !solutions.is_empty();
+        let solutions: Vec<_> = solutions
+            .into_iter()
+            .filter(|source| puzzle.execute(source, false, won))
+            .map(|source| (puzzle.execute(&source, false, state::steps), source))
+            .filter(|(steps, source)| config.fits(source, *steps))
+            .collect();
+        SolverResult {
+            // winners over the caps do not count as solutions
+            stop_reason: if sampled && solutions.is_empty() {
+                StopReason::GaveUp
+            } else {
+                stop_reason
+            },
+            solutions,
+            stats: SolverStats {
+                branches: rollouts,
+                steps: 0,
+                elapsed: start.elapsed(),
+                ..SolverStats::default()
+            },
+        }
+    }
+}
+
+/// Returns the winning programs found, the number of rollouts performed and
+/// why the search stopped. Progress is reported every `PROGRESS_INTERVAL`
+/// rollouts.
+pub fn monte_carlo(
+    puzzle: &Puzzle,
+    config: &SolverConfig,
+    start: Instant,
+) -> (Vec<Source>, u64, StopReason) {
     let mut root = Node::new(puzzle.empty_source());
     let mut best = NOGRAM;
-    fn recurse(puzzle: &Puzzle, node: &mut Node, best: &mut Source) -> f64 {
+    // rollouts past the step cap end there and score as losses
+    let limits = VmLimits {
+        max_steps: config
+            .step_cap
+            .map_or(usize::MAX, |cap| cap.saturating_add(1)),
+        ..VmLimits::default()
+    };
+    fn recurse(puzzle: &Puzzle, limits: VmLimits, node: &mut Node, best: &mut Source) -> f64 {
         node.rollouts += 1;
         let mut next: &mut Node = &mut Node::new(puzzle.empty_source());
         let mut score = 0.0;
         if node.is_leaf() {
-            if node.rollouts > 0 && node.have_children(puzzle) {
+            if node.rollouts > 0 && node.have_children(puzzle, limits) {
                 node.children[0].rollouts += 1;
-                score = rollout(puzzle, &mut node.children[0]);
+                score = rollout(puzzle, limits, &mut node.children[0]);
                 node.children[0].scores += score;
             } else {
-                score = rollout(puzzle, node);
+                score = rollout(puzzle, limits, node);
                 if score > 1.0 {
                     *best = node.source;
                 }
@@ -149,25 +206,41 @@ pub fn monte_carlo(puzzle: &Puzzle) -> Vec<Source> {
                     next = child;
                 }
             }
-            score = recurse(puzzle, next, best);
+            score = recurse(puzzle, limits, next, best);
         }
         node.scores += score;
         return score;
     }
     let mut score = 0.0;
-    let mut checks = 0;
+    let mut checks: u64 = 0;
     while score < 1.0 {
-        score = recurse(puzzle, &mut root, &mut best);
+        if let Some(reason) = interruption(config, start) {
+            return (vec![], checks, reason);
+        }
+        score = recurse(puzzle, limits, &mut root, &mut best);
         checks += 1;
-        if checks % (1 << 20) == 0 {
-            println!("{}", root);
+        if let Some(callback) = &config.progress {
+            if checks.is_multiple_of(PROGRESS_INTERVAL) {
+                callback.report(&Progress {
+                    branches: checks,
+                    stats: SolverStats {
+                        branches: checks,
+                        elapsed: start.elapsed(),
+                        ..SolverStats::default()
+                    },
+                    ..Progress::default()
+                });
+            }
+        }
+        // a root without children only ever replays the same rollout
+        if score < 1.0 && root.is_leaf() {
+            return (vec![], checks, StopReason::Exhausted);
         }
     }
-    println!("{}", best);
-    vec![best]
+    (vec![best], checks, StopReason::SolutionLimit)
 }
 
-fn rollout(puzzle: &Puzzle, node: &mut Node) -> f64 {
+fn rollout(puzzle: &Puzzle, limits: VmLimits, node: &mut Node) -> f64 {
     let mut candidate = node.source;
     let mut preferred = [true; 5];
     for i in 1..candidate.0.len() {
@@ -177,7 +250,7 @@ fn rollout(puzzle: &Puzzle, node: &mut Node) -> f64 {
             }
         }
     }
-    let mut state = puzzle.initial_state(&node.source);
+    let mut state = puzzle.initial_state(&node.source).with_limits(limits);
     let mut running = true;
     while running {
         let ins_pointer = state.ins_pointer();
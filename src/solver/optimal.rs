@@ -0,0 +1,193 @@
+use std::time::Instant;
+
+use super::backtrack::{explore, search_limits, Exploration, Frame};
+use super::{Progress, SolverConfig, SolverStats, StopReason};
+use crate::game::{puzzle::Puzzle, Source, MAX_STEPS};
+
+/// What makes one solution better than another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Objective {
+    /// Fewest instructions as counted by [`Source::count_ins`].
+    Instructions,
+    /// Fewest execution steps.
+    Steps,
+}
+
+/// How far the best solution is known to be optimal. Every claim is relative
+/// to the step budget, programs that need more steps are never considered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Optimality {
+    /// No better solution exists.
+    Proven,
+    /// The search stopped early, an optimal solution scores at least `lower`
+    /// and at most `upper` if a solution was found.
+    Bounded { lower: usize, upper: Option<usize> },
+}
+
+#[derive(Clone, Debug)]
+pub struct OptimalResult {
+    /// The best solution found as (steps, solution).
+    pub best: Option<(usize, Source)>,
+    pub optimality: Optimality,
+    pub step_budget: usize,
+    pub stats: SolverStats,
+    pub stop_reason: StopReason,
+}
+
+impl Objective {
+    pub fn score(&self, (steps, solution): &(usize, Source)) -> usize {
+        match self {
+            Objective::Instructions => solution.count_ins(),
+            Objective::Steps => *steps,
+        }
+    }
+}
+
+/// Searches for a solution that is optimal with respect to `objective` among
+/// all programs that finish within `config.step_cap` steps, [`MAX_STEPS`] by
+/// default.
+///
+/// The heuristic backtracker first provides an upper bound, which is then
+/// either improved or proven by exhaustive search: iterative deepening on
+/// the instruction count for [`Objective::Instructions`] and on a doubling
+/// step cap for [`Objective::Steps`]. Transpositions are never used.
+pub fn optimal(puzzle: Puzzle, objective: Objective, config: &SolverConfig) -> OptimalResult {
+    let start = Instant::now();
+    // the transposition table may prune unexplored programs, which would
+    // make the proof unsound
+    let config = &SolverConfig {
+        transpositions: false,
+        ..config.clone()
+    };
+    let step_budget = config.step_cap.unwrap_or(MAX_STEPS);
+    let heuristic = search_limits(
+        puzzle,
+        &SolverConfig {
+            max_solutions: None,
+            ..config.clone()
+        },
+        |_| (),
+    );
+    let mut stats = heuristic.stats;
+    let mut best = heuristic
+        .solutions
+        .into_iter()
+        .filter(|(steps, _)| *steps <= step_budget)
+        .min_by_key(|solution| (objective.score(solution), solution.0));
+    let slots = puzzle
+        .methods
+        .iter()
+        .sum::<usize>()
+        .min(config.instruction_cap.unwrap_or(usize::MAX));
+
+    let mut interrupted = match heuristic.stop_reason {
+        StopReason::Timeout | StopReason::Cancelled => Some(heuristic.stop_reason),
+        _ => None,
+    };
+    // every better solution scores at least this much
+    let mut lower = 1;
+    if interrupted.is_none() {
+        match objective {
+            Objective::Instructions => {
+                let upper = best
+                    .as_ref()
+                    .map_or(slots, |best| objective.score(best) - 1);
+                for instructions in 1..=upper {
+                    lower = instructions;
+                    let (found, reason) = exhaust(
+                        &puzzle,
+                        config,
+                        start,
+                        &mut stats,
+                        instructions,
+                        step_budget + 1,
+                    );
+                    interrupted = reason;
+                    if found.is_some() {
+                        best = found;
+                    }
+                    if best.is_some() || interrupted.is_some() {
+                        break;
+                    }
+                }
+            }
+            Objective::Steps => {
+                let upper = best.as_ref().map_or(step_budget + 1, |(steps, _)| *steps);
+                // every step collects at most one star, the cap doubles until
+                // it reaches the best solution so far
+                let mut cap = (puzzle.stars + 1).min(upper);
+                loop {
+                    let (found, reason) = exhaust(&puzzle, config, start, &mut stats, slots, cap);
+                    interrupted = reason;
+                    if found.is_some() {
+                        best = found;
+                    }
+                    if best.as_ref().is_some_and(|(steps, _)| *steps < cap)
+                        || interrupted.is_some()
+                        || cap == upper
+                    {
+                        break;
+                    }
+                    lower = cap;
+                    cap = (cap * 2).min(upper);
+                }
+            }
+        }
+    }
+    stats.elapsed = start.elapsed();
+    OptimalResult {
+        optimality: match interrupted {
+            None => Optimality::Proven,
+            Some(_) => Optimality::Bounded {
+                lower,
+                upper: best.as_ref().map(|best| objective.score(best)),
+            },
+        },
+        best,
+        step_budget,
+        stats,
+        stop_reason: interrupted.unwrap_or(StopReason::Exhausted),
+    }
+}
+
+/// Explores every program of at most `instructions` instructions that
+/// finishes in fewer than `step_cap` steps and returns the solution with the
+/// fewest steps among them.
+fn exhaust(
+    puzzle: &Puzzle,
+    config: &SolverConfig,
+    start: Instant,
+    stats: &mut SolverStats,
+    instructions: usize,
+    step_cap: usize,
+) -> (Option<(usize, Source)>, Option<StopReason>) {
+    let mut outer_frame = Frame::new(puzzle);
+    outer_frame.max_steps = step_cap.saturating_sub(1);
+    outer_frame.max_instructions = instructions;
+    outer_frame.candidate.shade(instructions);
+    let mut exploration = Exploration {
+        puzzle,
+        config,
+        start,
+        step_cap,
+        progress: Progress {
+            steps: step_cap.saturating_sub(1),
+            touches: usize::MAX,
+            instructions,
+            stats: *stats,
+            ..Progress::default()
+        },
+        steps: 0,
+    };
+    let mut best = None;
+    let reason = explore(&mut exploration, outer_frame, &mut |frame| {
+        let mut solution = frame.candidate;
+        solution.sanitize();
+        if best.is_none_or(|(steps, _)| frame.state.steps < steps) {
+            best = Some((frame.state.steps, solution));
+        }
+        true
+    });
+    *stats = exploration.progress.stats;
+    (best, reason)
+}
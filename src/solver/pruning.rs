@@ -7,7 +7,18 @@ use crate::game::puzzle::Puzzle;
 use crate::game::{instructions::*, *};
 use std::collections::HashSet;
 
-pub(crate) fn snip_around(puzzle: &Puzzle, temp: &Source, ins_pointer: InsPtr, show: bool) -> bool {
+pub(crate) fn snip_around<
+    const W: usize,
+    const H: usize,
+    const N: usize,
+    const M: usize,
+    P: InsPointer,
+>(
+    puzzle: &Puzzle<W, H, N, M, P>,
+    temp: &Source<N, M>,
+    ins_pointer: P,
+    show: bool,
+) -> bool {
     coz::begin!("snip around");
     let m = ins_pointer.get_method_index();
     let i = ins_pointer.get_ins_index();
@@ -38,22 +49,37 @@ pub(crate) fn snip_around(puzzle: &Puzzle, temp: &Source, ins_pointer: InsPtr, s
 }
 
 #[inline]
-pub(crate) fn deny(puzzle: &Puzzle, program: &Source, show: bool) -> bool {
+pub(crate) fn deny<
+    const W: usize,
+    const H: usize,
+    const N: usize,
+    const M: usize,
+    P: InsPointer,
+>(
+    puzzle: &Puzzle<W, H, N, M, P>,
+    program: &Source<N, M>,
+    show: bool,
+) -> bool {
     coz::begin!("deny");
     let result = deny_inner(puzzle, program, show);
     coz::end!("deny");
     return result;
 }
 
-fn deny_inner(puzzle: &Puzzle, program: &Source, show: bool) -> bool {
+fn deny_inner<const W: usize, const H: usize, const N: usize, const M: usize, P: InsPointer>(
+    puzzle: &Puzzle<W, H, N, M, P>,
+    program: &Source<N, M>,
+    show: bool,
+) -> bool {
     let mut denied = false;
-    let mut only_cond = [HALT, NOP, NOP, NOP, NOP];
-    let mut invoked = [0; 5];
+    let mut only_cond = [NOP; N];
+    only_cond[0] = HALT;
+    let mut invoked = [0; N];
     let mut has_nops = false;
-    let mut has_probe = [false; 5];
+    let mut has_probe = [false; N];
     // let mut has_forward = false;
     //    let starting_tile = *puzzle.initial_state(program).current_tile();
-    for m in 0..5 {
+    for m in 0..N {
         let mut halt_count = 0;
         for i in 0..puzzle.methods[m] {
             let ins = program[m][i];
@@ -69,7 +95,7 @@ fn deny_inner(puzzle: &Puzzle, program: &Source, show: bool) -> bool {
                     only_cond[ins.source_index()] = HALT;
                 }
                 let called = program[ins.source_index()];
-                denied |= called == [HALT; 10];
+                denied |= called == [HALT; M];
                 if show && denied {
                     println!("only halt {}", ins.source_index());
                     return true;
@@ -104,9 +130,9 @@ fn deny_inner(puzzle: &Puzzle, program: &Source, show: bool) -> bool {
     //         return true;
     //     }
     // }
-    for m in 0..5 {
+    for m in 0..N {
         let meth = program[m];
-        denied |= !program[m][0].is_halt() && program[m][1].is_halt();
+        denied |= M > 1 && !program[m][0].is_halt() && program[m][1].is_halt();
         if show && denied {
             println!("ghal");
             return true;
@@ -194,14 +220,36 @@ fn deny_inner(puzzle: &Puzzle, program: &Source, show: bool) -> bool {
 }
 
 #[inline]
-pub fn banned_pair(puzzle: &Puzzle, a: Ins, b: Ins, show: bool) -> bool {
+pub fn banned_pair<
+    const W: usize,
+    const H: usize,
+    const N: usize,
+    const M: usize,
+    P: InsPointer,
+>(
+    puzzle: &Puzzle<W, H, N, M, P>,
+    a: Ins,
+    b: Ins,
+    show: bool,
+) -> bool {
     coz::begin!("banned pair");
     let result = banned_pair_inner(b, a, show, puzzle);
     coz::end!("banned pair");
     return result;
 }
 
-fn banned_pair_inner(b: Ins, a: Ins, show: bool, puzzle: &Puzzle) -> bool {
+fn banned_pair_inner<
+    const W: usize,
+    const H: usize,
+    const N: usize,
+    const M: usize,
+    P: InsPointer,
+>(
+    b: Ins,
+    a: Ins,
+    show: bool,
+    puzzle: &Puzzle<W, H, N, M, P>,
+) -> bool {
     if b.is_halt() {
         return false;
     }
@@ -326,7 +374,19 @@ fn banned_pair_inner(b: Ins, a: Ins, show: bool, puzzle: &Puzzle) -> bool {
     banned || query_rejects_2(&[a, b])
 }
 
-pub fn banned_trio(puzzle: &Puzzle, a: Ins, b: Ins, c: Ins, show: bool) -> bool {
+pub fn banned_trio<
+    const W: usize,
+    const H: usize,
+    const N: usize,
+    const M: usize,
+    P: InsPointer,
+>(
+    puzzle: &Puzzle<W, H, N, M, P>,
+    a: Ins,
+    b: Ins,
+    c: Ins,
+    show: bool,
+) -> bool {
     coz::begin!("banned trio");
     if c.is_debug() {
         let result = banned_pair(puzzle, a, b, show);
@@ -354,7 +414,20 @@ pub fn banned_trio(puzzle: &Puzzle, a: Ins, b: Ins, c: Ins, show: bool) -> bool
     return result;
 }
 
-pub fn banned_quartet(puzzle: &Puzzle, a: Ins, b: Ins, c: Ins, d: Ins, show: bool) -> bool {
+pub fn banned_quartet<
+    const W: usize,
+    const H: usize,
+    const N: usize,
+    const M: usize,
+    P: InsPointer,
+>(
+    puzzle: &Puzzle<W, H, N, M, P>,
+    a: Ins,
+    b: Ins,
+    c: Ins,
+    d: Ins,
+    show: bool,
+) -> bool {
     coz::begin!("banned quartet");
     let result = if d == HALT {
         banned_trio(puzzle, a, b, c, show)
@@ -1,14 +1,32 @@
 use std::fs;
-use std::io::{Read, Write};
+use std::io::{ErrorKind, Read, Write};
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread::scope;
 use std::{fs::File, path::PathBuf};
 
+use crate::game::verify::{verify, VerifyReport};
 use crate::game::Source;
+use crate::solver::SolverStats;
 use crate::web::errors::SolverError;
+use crate::web::get_local_level;
 
 pub fn read_solution_from_file(puzzle_id: u64) -> Result<Vec<Source>, SolverError> {
     let mut path = PathBuf::from_str("data/solutions").expect("unable to create puzzle pathbuf");
     path.push(puzzle_id.to_string());
+    read_solutions_from_path(&path)
+}
+
+/// Whether solutions to the puzzle are cached in `data/solutions`.
+pub fn has_solution_file(puzzle_id: u64) -> bool {
+    Path::new("data/solutions")
+        .join(puzzle_id.to_string())
+        .is_file()
+}
+
+fn read_solutions_from_path(path: &Path) -> Result<Vec<Source>, SolverError> {
     File::options()
         .read(true)
         .open(path)
@@ -48,8 +66,151 @@ pub fn store_solutions_locally(solution: &Vec<Source>, puzzle_id: u64) {
         .unwrap();
 }
 
+/// Reads the effort of the last cached search for the puzzle, see
+/// [`store_stats_locally`].
+pub fn read_stats_from_file(puzzle_id: u64) -> Result<SolverStats, SolverError> {
+    let mut path = PathBuf::from_str("data/stats").expect("unable to create stats pathbuf");
+    path.push(puzzle_id.to_string());
+    let string = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&string)?)
+}
+
+/// Caches the effort of a search for the puzzle in `data/stats`, replacing
+/// earlier stats, whether or not the search found a solution.
+pub fn store_stats_locally(stats: &SolverStats, puzzle_id: u64) {
+    let mut path = PathBuf::from_str("data/stats").expect("unable to create stats pathbuf");
+    fs::create_dir_all(&path).expect("unable to create the stats directory");
+    path.push(puzzle_id.to_string());
+    fs::write(
+        path,
+        serde_json::to_string(stats).expect("should be able to convert stats to string"),
+    )
+    .unwrap_or_else(|err| {
+        eprintln!(
+            "unable to store stats {} locally.\nerror: {}",
+            puzzle_id, err
+        )
+    });
+}
+
 pub fn remove_solution_file(puzzle_id: u64) {
     let mut path = PathBuf::from_str("data/solutions").expect("unable to create solution pathbuf");
     path.push(puzzle_id.to_string());
     fs::remove_file(path).expect("should remove the file");
 }
+
+/// What replaying the cached solutions of one puzzle found.
+#[derive(Debug)]
+pub struct SolutionCheck {
+    pub puzzle_id: u64,
+    pub solutions: usize,
+    /// The index in the file and the report of every solution that doesn't
+    /// win or doesn't fit the puzzle.
+    pub invalid: Vec<(usize, VerifyReport)>,
+    /// There is no level with this id in `data/puzzles`.
+    pub orphaned: bool,
+    /// The solution file couldn't be read or fixed.
+    pub error: Option<SolverError>,
+}
+
+impl SolutionCheck {
+    pub fn passed(&self) -> bool {
+        self.invalid.is_empty() && !self.orphaned && self.error.is_none()
+    }
+}
+
+/// Replays every solution file in `directory` against its level on
+/// `threads` threads. With `fix`, invalid solutions are pruned from their
+/// files and files of orphaned ids or without any valid solution are
+/// removed.
+pub fn check_solutions(directory: &Path, fix: bool, threads: usize) -> Vec<SolutionCheck> {
+    let mut puzzle_ids: Vec<u64> = fs::read_dir(directory)
+        .expect("unable to read solution directory")
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .collect();
+    puzzle_ids.sort_unstable();
+    let next = AtomicUsize::new(0);
+    let checks = Mutex::new(Vec::with_capacity(puzzle_ids.len()));
+    scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| {
+                while let Some(&puzzle_id) = puzzle_ids.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let check =
+                        check_solution_file(&directory.join(puzzle_id.to_string()), puzzle_id, fix);
+                    checks.lock().expect("no checker panicked").push(check);
+                }
+            });
+        }
+    });
+    let mut checks = checks.into_inner().expect("no checker panicked");
+    checks.sort_unstable_by_key(|check| check.puzzle_id);
+    checks
+}
+
+fn check_solution_file(path: &Path, puzzle_id: u64, fix: bool) -> SolutionCheck {
+    let mut check = SolutionCheck {
+        puzzle_id,
+        solutions: 0,
+        invalid: vec![],
+        orphaned: false,
+        error: None,
+    };
+    let solutions = match read_solutions_from_path(path) {
+        Ok(solutions) => solutions,
+        Err(err) => {
+            check.error = Some(err);
+            return check;
+        }
+    };
+    check.solutions = solutions.len();
+    let level = match get_local_level(puzzle_id) {
+        Ok(level) => level,
+        Err(SolverError::NoPuzzleForId) => {
+            check.orphaned = true;
+            return fix_solution_file(path, check, fix, vec![]);
+        }
+        Err(SolverError::IOError(err)) if err.kind() == ErrorKind::NotFound => {
+            check.orphaned = true;
+            return fix_solution_file(path, check, fix, vec![]);
+        }
+        Err(err) => {
+            check.error = Some(err);
+            return check;
+        }
+    };
+    let mut valid = vec![];
+    for (index, solution) in solutions.into_iter().enumerate() {
+        let report = verify(&level.puzzle, &solution);
+        if report.passed() {
+            valid.push(solution);
+        } else {
+            check.invalid.push((index, report));
+        }
+    }
+    fix_solution_file(path, check, fix, valid)
+}
+
+fn fix_solution_file(
+    path: &Path,
+    check: SolutionCheck,
+    fix: bool,
+    valid: Vec<Source>,
+) -> SolutionCheck {
+    if !fix || check.passed() {
+        return check;
+    }
+    let fixed = if valid.is_empty() {
+        fs::remove_file(path)
+    } else {
+        serde_json::to_string(&valid)
+            .map_err(std::io::Error::from)
+            .and_then(|json| fs::write(path, json))
+    };
+    match fixed {
+        Ok(()) => check,
+        Err(err) => SolutionCheck {
+            error: Some(SolverError::IOError(err)),
+            ..check
+        },
+    }
+}
@@ -1,8 +1,29 @@
+use crate::collection::{
+    correlations, correlations_csv, features_csv, puzzle_table, Bounds, ListedPuzzle,
+    PuzzleFeatures, PuzzleQuery, SortKey,
+};
 use crate::constants::*;
-use crate::game::{instructions::*, state::won, Source};
+use crate::debugger::render;
+use crate::game::history::History;
+use crate::game::{
+    instructions::*,
+    notation::parse_program,
+    puzzle::make_puzzle,
+    state::{self, won},
+    Source, MAP_HEIGHT, MAP_WIDTH,
+};
 use crate::solver::backtrack::backtrack;
-use crate::solver::solutions::read_solution_from_file;
+use crate::solver::optimal::{optimal, Objective, Optimality};
+use crate::solver::pruning::{deny, snip_around};
+use crate::solver::solutions::{check_solutions, read_solution_from_file};
+use crate::solver::{
+    backtrack::{stream, Backtrack, BacktrackConfig},
+    backtrack_mt::ParallelBacktrack,
+    solver_by_name, CancelToken, ProgressCallback, Solver, SolverConfig, SolverStats, StopReason,
+};
 use crate::web::get_local_level;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 #[test]
 fn test_backtracker() {
@@ -23,3 +44,486 @@ fn test_backtracker() {
         assert!(!level.puzzle.execute(&TEST_SOURCE, false, won));
     }
 }
+
+#[test]
+fn test_solver_by_name() {
+    let level = get_local_level(23).expect("should have read solved local level");
+    let solver = solver_by_name("backtrack").expect("backtrack should be registered");
+    let config = SolverConfig {
+        max_solutions: Some(1),
+        ..SolverConfig::default()
+    };
+    let result = solver.solve(&level.puzzle, &config);
+    assert_eq!(result.stop_reason, StopReason::SolutionLimit);
+    assert_eq!(result.solutions.len(), 1);
+    assert!(result.stats.branches > 0);
+    for (steps, solution) in result.solutions {
+        assert_eq!(level.puzzle.execute(&solution, false, state::steps), steps);
+        assert!(level.puzzle.execute(&solution, false, won));
+    }
+    assert!(solver_by_name("no such solver").is_none());
+}
+
+#[test]
+fn test_backtrack_cancel_and_progress() {
+    let level = get_local_level(23).expect("should have read solved local level");
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let config = SolverConfig {
+        progress: Some({
+            let reports = reports.clone();
+            ProgressCallback::new(move |progress| reports.lock().unwrap().push(*progress))
+        }),
+        ..SolverConfig::default()
+    };
+    let result = Backtrack.solve(&level.puzzle, &config);
+    assert_eq!(result.stop_reason, StopReason::Exhausted);
+    let reports = reports.lock().unwrap();
+    let finished: Vec<_> = reports.iter().filter(|p| p.finished).collect();
+    assert!(!finished.is_empty());
+    assert!(finished
+        .iter()
+        .all(|p| p.branches > 0 && p.death_ratio() <= 1.0));
+    assert_eq!(
+        finished.iter().map(|p| p.branches).sum::<u64>(),
+        result.stats.branches
+    );
+    assert_eq!(finished.last().unwrap().solutions, result.solutions.len());
+
+    let config = SolverConfig::default();
+    config.cancel.cancel();
+    let result = Backtrack.solve(&level.puzzle, &config);
+    assert_eq!(result.stop_reason, StopReason::Cancelled);
+    assert_eq!(result.stats.branches, 1);
+}
+
+#[test]
+fn test_sampling_solvers_honour_config() {
+    let level = get_local_level(23).expect("should have read solved local level");
+    for name in ["carlo", "mcts"] {
+        let solver = solver_by_name(name).unwrap();
+        let config = SolverConfig::default();
+        config.cancel.cancel();
+        let result = solver.solve(&level.puzzle, &config);
+        assert_eq!(result.stop_reason, StopReason::Cancelled, "{}", name);
+        assert!(result.solutions.is_empty());
+
+        let config = SolverConfig {
+            max_solutions: Some(0),
+            ..SolverConfig::default()
+        };
+        let result = solver.solve(&level.puzzle, &config);
+        assert_eq!(result.stop_reason, StopReason::SolutionLimit, "{}", name);
+        assert!(result.solutions.is_empty());
+
+        let config = SolverConfig {
+            timeout: Some(Duration::from_secs(1)),
+            instruction_cap: Some(1),
+            step_cap: Some(1),
+            ..SolverConfig::default()
+        };
+        let result = solver.solve(&level.puzzle, &config);
+        assert!(result.solutions.is_empty(), "{}", name);
+    }
+}
+
+#[test]
+fn test_backtrack_stream() {
+    let level = get_local_level(23).expect("should have read solved local level");
+    let mut solutions = stream(level.puzzle, SolverConfig::default());
+    let first = solutions.next().expect("puzzle 23 is solvable");
+    assert!(level.puzzle.execute(&first.source, false, won));
+    assert_eq!(
+        level.puzzle.execute(&first.source, false, state::steps),
+        first.steps
+    );
+    assert!(first.source.count_ins() <= first.limit.instructions);
+    assert!(first.steps <= first.limit.steps);
+    drop(solutions);
+
+    // dropping a stream leaves the caller's token alone, cancelling the
+    // caller's token stops the stream
+    let config = SolverConfig::default();
+    drop(stream(level.puzzle, config.clone()));
+    assert!(!config.cancel.is_cancelled());
+    config.cancel.cancel();
+    let cancelled = stream(level.puzzle, config.clone()).into_result();
+    assert_eq!(cancelled.stop_reason, StopReason::Cancelled);
+
+    let streamed: Vec<_> = stream(level.puzzle, SolverConfig::default())
+        .map(|found| (found.steps, found.source))
+        .collect();
+    let result = Backtrack.solve(&level.puzzle, &SolverConfig::default());
+    assert_eq!(streamed.len(), result.solutions.len());
+    assert!(streamed
+        .iter()
+        .all(|found| result.solutions.contains(found)));
+}
+
+#[test]
+fn test_backtrack_transpositions() {
+    let config = SolverConfig {
+        transpositions: true,
+        ..SolverConfig::default()
+    };
+    let mut stats = vec![];
+    for id in [23, 24, 27, 45, 49, 58, 101, 114, 202, 264] {
+        let level = get_local_level(id).expect("should have read solved local level");
+        let plain = Backtrack.solve(&level.puzzle, &SolverConfig::default());
+        let result = Backtrack.solve(&level.puzzle, &config);
+        assert_eq!(result.stop_reason, StopReason::Exhausted);
+        assert!(!result.solutions.is_empty(), "puzzle {}", id);
+        for (steps, solution) in &result.solutions {
+            assert!(level.puzzle.execute(solution, false, won), "puzzle {}", id);
+            assert_eq!(level.puzzle.execute(solution, false, state::steps), *steps);
+        }
+        let fewest =
+            |solutions: &Vec<(usize, Source)>| solutions.iter().map(|(_, s)| s.count_ins()).min();
+        assert_eq!(
+            fewest(&plain.solutions),
+            fewest(&result.solutions),
+            "puzzle {}",
+            id
+        );
+        // every frame is looked up once
+        assert_eq!(
+            result.stats.transposition_hits + result.stats.transposition_misses,
+            result.stats.branches
+        );
+        assert_eq!(plain.stats.transposition_misses, 0);
+        stats.push(result.stats);
+    }
+    // puzzle 114 has programs that loop without running into a limit quickly
+    assert!(stats.iter().any(|stats| stats.cycles > 0));
+}
+
+#[test]
+fn test_backtrack_config() {
+    assert_eq!("".parse(), Ok(BacktrackConfig::default()));
+    let tuned: BacktrackConfig = "touch_scale=3, cap_factor=2".parse().unwrap();
+    assert_eq!(
+        tuned,
+        BacktrackConfig {
+            touch_scale: 3.0,
+            cap_factor: 2,
+            ..BacktrackConfig::default()
+        }
+    );
+    assert!("touch_scale".parse::<BacktrackConfig>().is_err());
+    assert!("touch_scale=fast".parse::<BacktrackConfig>().is_err());
+    assert!("speed=3".parse::<BacktrackConfig>().is_err());
+
+    let level = get_local_level(23).expect("should have read solved local level");
+    let default = Backtrack.solve(&level.puzzle, &SolverConfig::default());
+    let config = SolverConfig {
+        backtrack: "initial_touches=16,initial_instructions=4".parse().unwrap(),
+        ..SolverConfig::default()
+    };
+    let tuned = Backtrack.solve(&level.puzzle, &config);
+    assert_ne!(default.stats.branches, tuned.stats.branches);
+    assert!(!tuned.solutions.is_empty());
+    for (_, solution) in &tuned.solutions {
+        assert!(level.puzzle.execute(solution, false, won));
+    }
+}
+
+#[test]
+fn test_parallel_backtracker_matches() {
+    // without tightening the step cap the solutions found do not depend on
+    // the order the workers explore frames in
+    let single_config = SolverConfig {
+        backtrack: "tighten_steps=false".parse().unwrap(),
+        ..SolverConfig::default()
+    };
+    let config = SolverConfig {
+        threads: Some(4),
+        ..single_config.clone()
+    };
+    let mut several = 0;
+    for id in [
+        23, 24, 27, 28, 45, 46, 47, 49, 52, 58, 101, 123, 138, 147, 202, 220, 264,
+    ] {
+        let level = get_local_level(id).expect("should have read solved local level");
+        let mut single = Backtrack.solve(&level.puzzle, &single_config);
+        let mut parallel = ParallelBacktrack.solve(&level.puzzle, &config);
+        assert_eq!(parallel.stop_reason, StopReason::Exhausted);
+        for (steps, solution) in &parallel.solutions {
+            assert!(level.puzzle.execute(solution, false, won), "puzzle {}", id);
+            assert_eq!(level.puzzle.execute(solution, false, state::steps), *steps);
+        }
+        single.solutions.sort_unstable();
+        parallel.solutions.sort_unstable();
+        assert_eq!(single.solutions, parallel.solutions, "puzzle {}", id);
+        several += (single.solutions.len() > 1) as usize;
+    }
+    assert!(several > 0);
+}
+
+#[test]
+fn test_optimal_solutions() {
+    for id in [23, 24, 27, 45, 46, 52, 101, 123] {
+        let level = get_local_level(id).expect("should have read solved local level");
+        let heuristic = Backtrack.solve(&level.puzzle, &SolverConfig::default());
+        for objective in [Objective::Instructions, Objective::Steps] {
+            let result = optimal(level.puzzle, objective, &SolverConfig::default());
+            assert_eq!(result.optimality, Optimality::Proven, "puzzle {}", id);
+            let best = result.best.expect("solvable puzzle");
+            assert!(level.puzzle.execute(&best.1, false, won), "puzzle {}", id);
+            assert_eq!(level.puzzle.execute(&best.1, false, state::steps), best.0);
+            for solution in &heuristic.solutions {
+                assert!(objective.score(&best) <= objective.score(solution));
+            }
+        }
+    }
+    let level = get_local_level(1337).expect("should have read solved local level");
+    let config = SolverConfig {
+        timeout: Some(Duration::ZERO),
+        ..SolverConfig::default()
+    };
+    let result = optimal(level.puzzle, Objective::Instructions, &config);
+    assert_eq!(result.stop_reason, StopReason::Timeout);
+    assert!(matches!(result.optimality, Optimality::Bounded { .. }));
+
+    // cancelled once the step cap has doubled past the stars, the caps below
+    // it bound the optimum
+    let level = get_local_level(42).expect("should have read solved local level");
+    let cancel = CancelToken::new();
+    let stars = level.puzzle.stars;
+    let config = SolverConfig {
+        transpositions: true,
+        cancel: cancel.clone(),
+        progress: Some(ProgressCallback::new(move |progress| {
+            if progress.touches == usize::MAX && progress.steps > stars {
+                cancel.cancel();
+            }
+        })),
+        ..SolverConfig::default()
+    };
+    let result = optimal(level.puzzle, Objective::Steps, &config);
+    assert_eq!(result.stop_reason, StopReason::Cancelled);
+    assert_eq!(
+        result.optimality,
+        Optimality::Bounded {
+            lower: stars + 1,
+            upper: Some(47)
+        }
+    );
+    assert_eq!(result.stats.transposition_misses, 0);
+}
+
+#[test]
+fn test_debugger_render() {
+    let mut history = History::new(&PUZZLE_1337, PUZZLE_1337_SOLUTION);
+    let start = render(&history, "1337");
+    assert!(start.contains("step 0"));
+    assert!(start.contains("F1:["));
+    assert!(start.contains("stack: F1[0]"));
+
+    history.jump(3);
+    let pointer = *history.state().ins_pointer();
+    let frames = render(&history, "1337");
+    assert!(frames.contains("step 3"));
+    assert!(frames.contains(&format!(
+        "stack: F{}[{}]",
+        pointer.get_method_index() + 1,
+        pointer.get_ins_index()
+    )));
+    history.jump(usize::MAX);
+    assert!(render(&history, "1337").contains("won"));
+}
+
+#[test]
+fn test_check_solutions() {
+    let directory = std::env::temp_dir().join(format!("solutions-check-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let mut solutions = read_solution_from_file(101).unwrap();
+    let valid = solutions.len();
+    solutions.insert(1, TEST_SOURCE);
+    let write = |id: &str, solutions: &Vec<Source>| {
+        std::fs::write(
+            directory.join(id),
+            serde_json::to_string(solutions).unwrap(),
+        )
+        .unwrap()
+    };
+    write("101", &solutions);
+    write("103", &read_solution_from_file(103).unwrap());
+    // 64 is stored as `null` in data/puzzles
+    write("64", &solutions);
+    std::fs::write(directory.join("105"), "[[").unwrap();
+
+    let checks = check_solutions(&directory, false, 2);
+    assert_eq!(
+        checks
+            .iter()
+            .map(|check| check.puzzle_id)
+            .collect::<Vec<_>>(),
+        [64, 101, 103, 105]
+    );
+    assert!(checks[0].orphaned);
+    assert_eq!(checks[1].invalid.len(), 1);
+    assert_eq!(checks[1].invalid[0].0, 1);
+    assert!(!checks[1].invalid[0].1.won);
+    assert!(checks[2].passed());
+    assert!(checks[3].error.is_some());
+
+    check_solutions(&directory, true, 2);
+    assert!(!directory.join("64").exists());
+    let fixed: Vec<Source> =
+        serde_json::from_str(&std::fs::read_to_string(directory.join("101")).unwrap()).unwrap();
+    assert_eq!(fixed.len(), valid);
+    assert!(!fixed.contains(&TEST_SOURCE));
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_wide_program_pruning() {
+    let methods = [3, 4, 2, 2, 2, 2, 2];
+    let puzzle =
+        make_puzzle::<MAP_WIDTH, MAP_HEIGHT, 7, 12, InsPtr>(PUZZLE_42.board, methods, [true; 3]);
+    let program = |text: &str| parse_program(text, &puzzle).unwrap();
+    let allowed = program("F1: f R7 G6 | F2: f < | F6: f | F7: f f");
+    assert!(!deny(&puzzle, &allowed, false));
+    // F7 is called but does nothing
+    assert!(deny(
+        &puzzle,
+        &program("F1: f R7 G6 | F2: f < | F6: f"),
+        false
+    ));
+    // turning back and forth in F7
+    let wiggle = program("F1: f R7 G6 | F2: f < | F6: f | F7: < >");
+    assert!(snip_around(&puzzle, &wiggle, InsPtr::new(6, 1), false));
+    assert!(!snip_around(&puzzle, &allowed, InsPtr::new(6, 1), false));
+}
+
+#[test]
+fn test_puzzle_features() {
+    let level = get_local_level(42).unwrap();
+    let stats = SolverStats {
+        branches: 12,
+        steps: 340,
+        ..SolverStats::default()
+    };
+    let features = PuzzleFeatures::new(&level, &[PUZZLE_42_SOLUTION], Some(&stats));
+    assert_eq!(features.tiles, level.puzzle.board.count_tiles());
+    assert_eq!((features.colors, features.marks), (1, 0));
+    assert_eq!((features.methods, features.slots), (4, 11));
+    assert_eq!(features.difficulty, Some(2809.0 / 1190.0));
+    assert_eq!(
+        features.solution_steps,
+        Some(PUZZLE_42.execute(&PUZZLE_42_SOLUTION, false, state::steps))
+    );
+    assert_eq!(features.solution_instructions, Some(11));
+    assert_eq!(
+        (features.branches, features.search_steps),
+        (Some(12), Some(340))
+    );
+    let unsolved = PuzzleFeatures::new(&level, &[], None);
+    assert!(!unsolved.solved() && unsolved.branches.is_none());
+
+    // the difficulty rises with the slots and falls with the stars
+    let corpus: Vec<_> = (0..5)
+        .map(|i| PuzzleFeatures {
+            id: i,
+            slots: 10 + i as usize * 3,
+            stars: 20 - i as usize * i as usize,
+            difficulty: Some(1.0 + i as f64),
+            ..unsolved.clone()
+        })
+        .collect();
+    let correlations = correlations(&corpus);
+    let by_name = |name| correlations.iter().find(|c| c.feature == name).unwrap();
+    assert_eq!(by_name("slots").samples, 5);
+    assert!((by_name("slots").pearson.unwrap() - 1.0).abs() < 1e-9);
+    assert!((by_name("stars").spearman.unwrap() + 1.0).abs() < 1e-9);
+    assert!(by_name("stars").pearson.unwrap() > -1.0);
+    assert_eq!(by_name("tiles").pearson, None);
+    assert_eq!(by_name("branches").samples, 0);
+
+    let csv = features_csv(&[PuzzleFeatures {
+        title: "Say \"hi\", robot".to_string(),
+        ..features
+    }]);
+    let row = csv.lines().nth(1).unwrap();
+    assert!(row.starts_with("42,\"Say \"\"hi\"\", robot\","));
+    assert!(row.ends_with(",11,12,340"));
+    assert_eq!(
+        correlations_csv(&correlations).lines().count(),
+        correlations.len() + 1
+    );
+}
+
+#[test]
+fn test_puzzle_query() {
+    assert_eq!(
+        "7".parse(),
+        Ok(Bounds {
+            min: Some(7),
+            max: Some(7)
+        })
+    );
+    assert_eq!(
+        "3..9".parse(),
+        Ok(Bounds {
+            min: Some(3),
+            max: Some(9)
+        })
+    );
+    assert_eq!(
+        "..=9".parse(),
+        Ok(Bounds {
+            min: None,
+            max: Some(9)
+        })
+    );
+    assert_eq!(
+        "3..".parse(),
+        Ok(Bounds {
+            min: Some(3),
+            max: None
+        })
+    );
+    assert!("x..9".parse::<Bounds>().is_err() && "".parse::<Bounds>().is_err());
+
+    let levels = || [42, 536].into_iter().map(|id| get_local_level(id).unwrap());
+    let select = |query: PuzzleQuery| -> Vec<u64> {
+        query
+            .select(levels(), |id| id == 42)
+            .iter()
+            .map(|level| level.id)
+            .collect()
+    };
+    assert_eq!(select(PuzzleQuery::default()), vec![42, 536]);
+    let query = PuzzleQuery {
+        sort: Some(SortKey::Difficulty),
+        descending: true,
+        ..PuzzleQuery::default()
+    };
+    assert_eq!(select(query), vec![536, 42]);
+    let query = PuzzleQuery {
+        title: Some("POWERS".to_string()),
+        ..PuzzleQuery::default()
+    };
+    assert_eq!(select(query), vec![42]);
+    let query = PuzzleQuery {
+        featured: Some(false),
+        ..PuzzleQuery::default()
+    };
+    assert_eq!(select(query), vec![536]);
+    let query = PuzzleQuery {
+        solved: Some(false),
+        ..PuzzleQuery::default()
+    };
+    assert_eq!(select(query), vec![536]);
+    let query = PuzzleQuery {
+        ids: "..100".parse().unwrap(),
+        slots: "11".parse().unwrap(),
+        ..PuzzleQuery::default()
+    };
+    assert_eq!(select(query), vec![42]);
+
+    let listed = ListedPuzzle::new(&get_local_level(42).unwrap(), true);
+    assert_eq!((listed.colors, listed.marks, listed.slots), (1, 0, 11));
+    let table = puzzle_table(&[listed]);
+    assert_eq!(table.lines().count(), 2);
+    assert!(table.lines().nth(1).unwrap().contains("The powers"));
+}
@@ -1,6 +1,6 @@
 use std::fs::File;
 use std::io::{prelude::*, ErrorKind, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::thread::sleep;
 use std::time::Duration;
@@ -16,14 +16,20 @@ use crate::constants::*;
 use crate::game::board::Board;
 use crate::game::{
     instructions::*,
+    notation::{parse_program, ProgramError},
     puzzle::{make_puzzle, Puzzle},
-    Direction, Source, Tile,
+    Direction, Map, Source, Tile, STANDARD_METHODS, STANDARD_METHOD_LENGTH,
 };
 use crate::solver::backtrack::backtrack;
 
+use self::credentials::Credentials;
 use self::errors::SolverError;
+use self::service::ServiceClient;
 
+pub mod credentials;
 pub mod errors;
+pub mod index;
+pub mod service;
 #[cfg(test)]
 mod tests;
 
@@ -37,16 +43,13 @@ enum Solution {
     Solved(Puzzle, Source),
 }
 
-// username: Hugsun
-// password: 8r4WvSfxHGirMDxH6FBO
-
-pub fn solve_puzzle(puzzle_id: u64, login: bool) -> Result<(), SolverError> {
+pub fn solve_puzzle(puzzle_id: u64, login: Option<&Credentials>) -> Result<(), SolverError> {
     let rt = Runtime::new()?;
     rt.block_on(async {
         let driver = get_driver().await?;
 
-        if login {
-            perform_login(&driver).await?
+        if let Some(credentials) = login {
+            perform_login(&driver, credentials).await?
         }
         let mut url = goto_puzzle_url(puzzle_id, &driver).await;
 
@@ -60,7 +63,7 @@ pub fn solve_puzzle(puzzle_id: u64, login: bool) -> Result<(), SolverError> {
             println!("Trying solution: {}", solution.1);
             println!("that takes {} steps", solution.0);
             url.push_str("&program=");
-            url.push_str(encode_program(&solution.1, &puzzle).as_str());
+            url.push_str(&encode_program(&solution.1, &puzzle).map_err(SolverError::from)?);
             driver.goto(url).await?;
 
             driver
@@ -91,27 +94,14 @@ pub fn get_levels(
         Ok(level) => result.push(Ok(level)),
         Err(serr) => match serr {
             SolverError::NoPuzzleForId => result.push(Err(serr)),
-            SolverError::IOError(ierr) => {
-                if ierr.kind() == ErrorKind::NotFound {
-                    remaining_puzzle_ids.push(puzzle_id);
-                } else {
-                    panic!("{:?}, IOError kind: {}", ierr, ierr.kind())
-                }
+            SolverError::IOError(ierr) if ierr.kind() == ErrorKind::NotFound => {
+                remaining_puzzle_ids.push(puzzle_id)
             }
-            _ => panic!("{:?}", serr),
+            _ => result.push(Err(serr)),
         },
     });
     if remaining_puzzle_ids.len() > 0 {
-        let rt = Runtime::new().unwrap();
-        rt.block_on(async {
-            // let driver = get_driver().await.unwrap();
-            result.append(
-                &mut get_external_levels(remaining_puzzle_ids.into_iter())
-                    .await
-                    .collect(),
-            );
-            // driver.quit().await
-        });
+        result.extend(get_external_levels(remaining_puzzle_ids.into_iter()));
     }
     return result.into_iter();
 }
@@ -135,7 +125,7 @@ async fn get_driver() -> Result<WebDriver, SolverError> {
     Ok(driver)
 }
 
-async fn perform_login(driver: &WebDriver) -> Result<(), SolverError> {
+async fn perform_login(driver: &WebDriver, credentials: &Credentials) -> Result<(), SolverError> {
     driver
         .goto("http://www.robozzle.com/beta/index.html")
         .await
@@ -158,10 +148,13 @@ async fn perform_login(driver: &WebDriver) -> Result<(), SolverError> {
         .unwrap();
 
     let signin_form = driver.form(By::Id("dialog-signin")).await.unwrap();
-    signin_form.set_by_name("name", "Hugsun").await.unwrap();
+    signin_form
+        .set_by_name("name", &credentials.username)
+        .await
+        .unwrap();
     sleep(Duration::from_millis(500));
     signin_form
-        .set_by_name("password", "8r4WvSfxHGirMDxH6FBO")
+        .set_by_name("password", &credentials.password)
         .await
         .unwrap();
     sleep(Duration::from_millis(500));
@@ -178,62 +171,39 @@ async fn goto_puzzle_url(puzzle_id: u64, driver: &WebDriver) -> String {
     url
 }
 
-async fn get_external_levels(
+fn get_external_levels(
     puzzle_ids: impl Iterator<Item = u64>,
 ) -> impl Iterator<Item = Result<Level, SolverError>> {
-    let driver = get_driver().await.unwrap();
-    let url = "http://www.robozzle.com/beta/index.html".to_string();
-    driver.goto(url.as_str()).await.unwrap();
-    // Disable top solver fetching
-    driver
-        .execute("robozzle.topSolvers = () => {};", vec![])
-        .await
-        .unwrap();
+    let client = match ServiceClient::from_env() {
+        Ok(client) => client,
+        Err(err) => return vec![Err(err)].into_iter(),
+    };
     let mut result = Vec::new();
     for puzzle_id in puzzle_ids {
-        println!("Fetching puzzle");
-        // switch to different level
-        let boi = get_on_page_level(&driver, puzzle_id, true).await;
-
-        result.push(boi.map_err(SolverError::from));
+        result.push(fetch_level(&client, puzzle_id));
     }
-    driver.quit().await.unwrap();
     return result.into_iter();
 }
 
-async fn get_on_page_level(
-    driver: &WebDriver,
-    puzzle_id: u64,
-    store_locally: bool,
-) -> Result<Level, serde_json::Error> {
-    let json = driver
-        .execute_async(
-            &format!(
-                "
-                    let done = arguments[0];
-                    robozzle.service('GetLevel',
-                    {{levelId: {}}},
-                    function (result, response) {{
-                        done(response.GetLevelResult);
-                    }});",
-                puzzle_id
-            ),
-            Vec::new(),
-        )
-        .await
-        .unwrap();
-    let json = json.json();
-    if store_locally {
-        store_puzzle_locally(&json.to_string(), puzzle_id);
-    }
-    println!("level: {}", json.to_string());
-    serde_json::from_value::<LevelJson>(json.clone()).map(Level::from)
+fn fetch_level(client: &ServiceClient, puzzle_id: u64) -> Result<Level, SolverError> {
+    let json = client.get_level_json(puzzle_id)?;
+    store_puzzle_locally(&serde_json::to_string_pretty(&json)?, puzzle_id);
+    serde_json::from_value::<Option<LevelJson>>(json)?
+        .ok_or(SolverError::NoPuzzleForId)
+        .and_then(Level::try_from)
 }
 
 pub fn get_local_level(puzzle_id: u64) -> Result<Level, SolverError> {
     let mut path = PathBuf::from_str("data/puzzles").expect("unable to create puzzle pathbuf");
     path.push(puzzle_id.to_string());
-    read_level_from_path(path)
+    let indexed = match index::loaded_local_index() {
+        Some(index) => index
+            .lock()
+            .expect("puzzle index lock poisoned")
+            .get(&path, &std::fs::metadata(&path)?),
+        None => None,
+    };
+    indexed.unwrap_or_else(|| read_level_from_path(path))
 }
 
 fn get_local_levels(
@@ -253,41 +223,32 @@ fn read_level_from_path(path: PathBuf) -> Result<Level, SolverError> {
             let level_json: Result<Level, _> = serde_json::from_str::<Option<LevelJson>>(&string)
                 .map_err(SolverError::Serde)
                 .and_then(|opt| opt.ok_or(SolverError::NoPuzzleForId))
-                .map(Level::from);
+                .and_then(Level::try_from);
             return level_json;
         })
 }
 
+/// Every level in `data/puzzles`, parsing only the files that changed since
+/// the last call and storing the index at [`index::INDEX_PATH`].
 pub fn get_all_local_levels() -> impl Iterator<Item = Level> {
-    // let mut result = Vec::new();
-    std::fs::read_dir("data/puzzles")
-        .expect("unable to read puzzle directory")
-        .filter_map(|dir| {
-            let path = dir.expect("unable to read dir").path();
-            read_level_from_path(path.clone())
-                .map_err(|err| {
-                    eprintln!(
-                        "Read level from path error: {:?}\n reading pathh: {:?}",
-                        err, path
-                    )
-                })
-                .ok()
-        })
-    // for dir in std::fs::read_dir("data/puzzles").expect("unable to read puzzle directory") {
-    //     let path = dir.expect("unable to read dir").path();
-    //     let level_json = File::options()
-    //         .read(true)
-    //         .open(path)
-    //         .map(|mut file| {
-    //             let mut string = String::new();
-    //             file.read_to_string(&mut string);
-    //             let level_json: LevelJson =
-    //                 serde_json::from_str(&string).expect("couldn't deserialize JSON");
-    //             return Level::from(level_json);
-    //         })
-    //         .expect("should be opening an existing file");
-    //     result.push(level_json);
-    // }
+    let mut index = index::local_index()
+        .lock()
+        .expect("puzzle index lock poisoned");
+    let refresh = index
+        .refresh(Path::new("data/puzzles"))
+        .expect("unable to read puzzle directory");
+    for (path, err) in refresh.errors.iter() {
+        eprintln!(
+            "Read level from path error: {:?}\n reading pathh: {:?}",
+            err, path
+        );
+    }
+    if refresh.changed {
+        if let Err(err) = index.store(Path::new(index::INDEX_PATH)) {
+            eprintln!("unable to store the puzzle index: {:?}", err);
+        }
+    }
+    refresh.levels.into_iter()
 }
 
 fn store_puzzle_locally(json: &str, puzzle_id: u64) {
@@ -313,46 +274,141 @@ fn store_puzzle_locally(json: &str, puzzle_id: u64) {
 pub fn puzzle_from_string(string: &str) -> Puzzle {
     let level_json: LevelJson = serde_json::from_str(string)
         .unwrap_or_else(|err| panic!("couldn't read JSON: {}\n error: {}", string, err));
-    return level_json_to_puzzle(&level_json);
+    return level_json_to_puzzle(&level_json)
+        .unwrap_or_else(|err| panic!("invalid level: {}\n error: {:?}", string, err));
 }
 
-fn level_json_to_puzzle(level_json: &LevelJson) -> Puzzle {
-    let mut map = PUZZLE_NULL.board.map.clone();
-    for y in 0..12 {
+const MAX_SUBLENGTH: usize = 10;
+
+impl LevelJson {
+    fn invalid(&self, field: &'static str, reason: impl Into<String>) -> SolverError {
+        SolverError::InvalidLevel {
+            id: self.Id.clone(),
+            field,
+            reason: reason.into(),
+        }
+    }
+    fn parse_field<T: FromStr>(&self, field: &'static str, value: &str) -> Result<T, SolverError> {
+        value
+            .parse::<T>()
+            .map_err(|_| self.invalid(field, format!("can't parse '{}'", value)))
+    }
+    fn rows(
+        &self,
+        field: &'static str,
+        rows: &[String],
+        width: usize,
+        height: usize,
+    ) -> Result<(), SolverError> {
+        if rows.len() != height {
+            return Err(self.invalid(
+                field,
+                format!("expected {} rows, found {}", height, rows.len()),
+            ));
+        }
+        match rows.iter().position(|row| row.chars().count() != width) {
+            Some(y) => Err(self.invalid(field, format!("row {} is not {} tiles wide", y, width))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Reads a puzzle in the level format of robozzle from `string`, with a board
+/// of `W - 2` by `H - 2` tiles instead of the standard size.
+pub fn custom_puzzle_from_string<const W: usize, const H: usize>(
+    string: &str,
+) -> Result<Puzzle<W, H>, SolverError> {
+    level_json_to_puzzle(&serde_json::from_str(string)?)
+}
+
+fn level_json_to_puzzle<const W: usize, const H: usize>(
+    level_json: &LevelJson,
+) -> Result<Puzzle<W, H>, SolverError> {
+    let (width, height) = (Map::<W, H>::WIDTH, Map::<W, H>::HEIGHT);
+    level_json.rows("Colors", &level_json.Colors, width, height)?;
+    level_json.rows("Items", &level_json.Items, width, height)?;
+    let mut map = Map::<W, H>::empty();
+    for y in 0..height {
         let mut cols = level_json.Colors[y].chars();
         let mut tems = level_json.Items[y].chars();
-        for x in 0..16 {
-            let color = match cols.next().unwrap_or(' ') {
-                'R' => RE,
-                'G' => GE,
-                'B' => BE,
-                _ => _N,
+        for x in 0..width {
+            let (col, tem) = (cols.next().unwrap(), tems.next().unwrap());
+            let color = match (col, tem) {
+                (_, '#') => _N,
+                ('R', _) => RE,
+                ('G', _) => GE,
+                ('B', _) => BE,
+                _ => {
+                    return Err(level_json.invalid(
+                        "Colors",
+                        format!("unknown color '{}' at row {}, column {}", col, y, x),
+                    ))
+                }
             };
-            map.0[y + 1][x + 1] = match tems.next().unwrap_or(' ') {
+            map.0[y + 1][x + 1] = match tem {
                 '*' => Tile(color.0 | TILE_STAR_MASK.0),
-                '.' => color,
-                _ => _N,
+                '.' | '#' => color,
+                _ => {
+                    return Err(level_json.invalid(
+                        "Items",
+                        format!("unknown item '{}' at row {}, column {}", tem, y, x),
+                    ))
+                }
             }
         }
     }
-    let direction = match level_json.RobotDir.chars().next().unwrap_or(' ') {
-        '0' => Direction::Right,
-        '1' => Direction::Down,
-        '2' => Direction::Left,
-        '3' => Direction::Up,
-        _ => Direction::Up,
+    let direction = match level_json.RobotDir.as_str() {
+        "0" => Direction::Right,
+        "1" => Direction::Down,
+        "2" => Direction::Left,
+        "3" => Direction::Up,
+        dir => return Err(level_json.invalid("RobotDir", format!("unknown direction '{}'", dir))),
     };
+    if level_json.SubLengths.len() != 5 {
+        return Err(level_json.invalid(
+            "SubLengths",
+            format!("expected 5 methods, found {}", level_json.SubLengths.len()),
+        ));
+    }
     let mut methods = [0; 5];
     for m in 0..5 {
-        methods[m] = level_json.SubLengths[m].parse().unwrap();
+        methods[m] = level_json.parse_field("SubLengths", &level_json.SubLengths[m])?;
+        if methods[m] > MAX_SUBLENGTH {
+            return Err(level_json.invalid(
+                "SubLengths",
+                format!(
+                    "F{} has {} slots, at most {} are allowed",
+                    m + 1,
+                    methods[m],
+                    MAX_SUBLENGTH
+                ),
+            ));
+        }
+    }
+    let mflags: u8 = level_json.parse_field("AllowedCommands", &level_json.AllowedCommands)?;
+    if mflags > 0b111 {
+        return Err(level_json.invalid("AllowedCommands", format!("unknown flags {}", mflags)));
+    }
+    let x: usize = level_json.parse_field("RobotCol", &level_json.RobotCol)?;
+    let y: usize = level_json.parse_field("RobotRow", &level_json.RobotRow)?;
+    if x >= width || y >= height {
+        return Err(level_json.invalid(
+            if x >= width { "RobotCol" } else { "RobotRow" },
+            format!("robot at row {}, column {} is outside the board", y, x),
+        ));
     }
-    let mflags: u8 = level_json.AllowedCommands.parse().unwrap();
-    return make_puzzle(
+    if map.0[y + 1][x + 1] == _N {
+        return Err(level_json.invalid(
+            "Items",
+            format!("robot at row {}, column {} is on an empty tile", y, x),
+        ));
+    }
+    return Ok(make_puzzle(
         Board {
             map,
             direction,
-            x: level_json.RobotCol.parse::<usize>().unwrap() + 1,
-            y: level_json.RobotRow.parse::<usize>().unwrap() + 1,
+            x: x + 1,
+            y: y + 1,
         },
         methods,
         [
@@ -360,10 +416,10 @@ fn level_json_to_puzzle(level_json: &LevelJson) -> Puzzle {
             (mflags & 0b10) > 0,
             (mflags & 0b100) > 0,
         ],
-    );
+    ));
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Level {
     pub about: Value,
     pub comment_count: u64,
@@ -380,41 +436,35 @@ pub struct Level {
     pub puzzle: Puzzle,
 }
 
-impl From<LevelJson> for Level {
-    fn from(value: LevelJson) -> Self {
-        Level {
-            puzzle: level_json_to_puzzle(&value),
-            about: value.About,
-            comment_count: value
-                .CommentCount
-                .parse::<u64>()
-                .expect("comment count should be u64"),
+impl Level {
+    /// The mean community difficulty vote, `None` without votes.
+    pub fn difficulty(&self) -> Option<f64> {
+        (self.difficulty_vote_count > 0)
+            .then(|| self.difficulty_vote_sum as f64 / self.difficulty_vote_count as f64)
+    }
+}
+
+impl TryFrom<LevelJson> for Level {
+    type Error = SolverError;
+
+    fn try_from(value: LevelJson) -> Result<Self, Self::Error> {
+        Ok(Level {
+            puzzle: level_json_to_puzzle(&value)?,
+            comment_count: value.parse_field("CommentCount", &value.CommentCount)?,
             difficulty_vote_count: value
-                .DifficultyVoteCount
-                .parse::<u64>()
-                .expect("difficulty vote count should be u64"),
+                .parse_field("DifficultyVoteCount", &value.DifficultyVoteCount)?,
             difficulty_vote_sum: value
-                .DifficultyVoteSum
-                .parse::<u64>()
-                .expect("difficulty vote sum should be u64"),
-            dislikes: value
-                .Disliked
-                .parse::<u64>()
-                .expect("dislikes should be u64"),
-            featured: value
-                .Featured
-                .parse::<bool>()
-                .expect("featured should be a boolean"),
-            id: value.Id.parse::<u64>().expect("puzzle id should be u64"),
-            likes: value.Liked.parse::<u64>().expect("likes should be u64"),
-            solution_count: value
-                .Solutions
-                .parse::<u64>()
-                .expect("solution count should be u64"),
-            submitted_by: value.SubmittedBy,
+                .parse_field("DifficultyVoteSum", &value.DifficultyVoteSum)?,
+            dislikes: value.parse_field("Disliked", &value.Disliked)?,
+            featured: value.parse_field("Featured", &value.Featured)?,
+            id: value.parse_field("Id", &value.Id)?,
+            likes: value.parse_field("Liked", &value.Liked)?,
+            solution_count: value.parse_field("Solutions", &value.Solutions)?,
+            about: value.About,
+            submitted_by: value.SubmittedBy.as_str().map(str::to_string),
             submitted_date: value.SubmittedDate,
             title: value.Title,
-        }
+        })
     }
 }
 
@@ -438,7 +488,9 @@ pub struct LevelJson {
     RobotRow: String,
     Solutions: String,
     SubLengths: Vec<String>,
-    SubmittedBy: Option<String>,
+    /// A string, or `{}` when the author is nil.
+    #[serde(default)]
+    SubmittedBy: Value,
     SubmittedDate: String,
     Title: String,
 }
@@ -514,24 +566,102 @@ impl StateEncoder {
     }
 }
 
-fn actualize_solution(program: &Source, puzzle: &Puzzle) -> Source {
-    let mut result = *program;
-    if puzzle.methods != puzzle.actual_methods {
-        let (mut mapping, mut invmap) = ([5; 5], [5; 5]);
-        let mut marked = [false; 5];
-        for i in 0..5 {
-            for j in 0..5 {
-                if puzzle.actual_methods[i] == puzzle.methods[j] && !marked[j] {
-                    mapping[i] = j;
-                    marked[j] = true;
-                    break;
+struct StateDecoder<'a> {
+    input: std::str::Chars<'a>,
+    val: usize,
+    bits: usize,
+}
+
+impl StateDecoder<'_> {
+    fn decode_bits(&mut self, bits: usize) -> Result<usize, ProgramError> {
+        let mut result = 0;
+        for i in 0..bits {
+            if self.bits == 6 {
+                let c = self.input.next().ok_or(ProgramError::Truncated)?;
+                self.val = match c {
+                    'a'..='z' => c as usize - 97,
+                    'A'..='Z' => c as usize - 65 + 26,
+                    '0'..='9' => c as usize - 48 + 52,
+                    '-' => 62,
+                    '_' => 63,
+                    _ => return Err(ProgramError::InvalidCharacter(c)),
+                };
+                self.bits = 0;
+            }
+            result |= ((self.val >> self.bits) & 1) << i;
+            self.bits += 1;
+        }
+        Ok(result)
+    }
+    /// Reads a command of a program with `methods` methods.
+    fn decode_command(&mut self, methods: usize) -> Result<Ins, ProgramError> {
+        let cond = [GRAY_COND, RED_COND, GREEN_COND, BLUE_COND][self.decode_bits(2)?];
+        let ins = match self.decode_bits(3)? {
+            0 => return Ok(HALT),
+            1 => FORWARD,
+            2 => LEFT,
+            3 => RIGHT,
+            4 => match self.decode_bits(3)? {
+                function if function < methods => Ins::fun_from_index(function),
+                function => {
+                    return Err(ProgramError::Malformed(format!(
+                        "call to unknown function {}",
+                        function + 1
+                    )))
                 }
+            },
+            5 => match self.decode_bits(2)? {
+                1 => MARK_RED,
+                2 => MARK_GREEN,
+                3 => MARK_BLUE,
+                _ => return Err(ProgramError::Malformed("paint without a color".to_string())),
+            },
+            command => {
+                return Err(ProgramError::Malformed(format!(
+                    "unknown command code {}",
+                    command
+                )))
+            }
+        };
+        Ok(ins | cond)
+    }
+}
+
+/// mapping[m] is the solver's index of the site's method m and invmap is its inverse.
+fn method_mapping<const W: usize, const H: usize, const N: usize, const M: usize, P: InsPointer>(
+    puzzle: &Puzzle<W, H, N, M, P>,
+) -> ([usize; N], [usize; N]) {
+    let (mut mapping, mut invmap) = ([N; N], [N; N]);
+    let mut marked = [false; N];
+    for i in 0..N {
+        for j in 0..N {
+            if puzzle.actual_methods[i] == puzzle.methods[j] && !marked[j] {
+                mapping[i] = j;
+                marked[j] = true;
+                break;
             }
-            invmap[mapping[i]] = i;
         }
-        for m in 0..5 {
+        invmap[mapping[i]] = i;
+    }
+    (mapping, invmap)
+}
+
+fn actualize_solution<
+    const W: usize,
+    const H: usize,
+    const N: usize,
+    const M: usize,
+    P: InsPointer,
+>(
+    program: &Source<N, M>,
+    puzzle: &Puzzle<W, H, N, M, P>,
+) -> Source<N, M> {
+    let mut result = *program;
+    if puzzle.methods != puzzle.actual_methods {
+        let (mapping, invmap) = method_mapping(puzzle);
+        for m in 0..N {
             result[m] = program[mapping[m]];
-            for i in 0..10 {
+            for i in 0..M {
                 if result[m][i].is_function() {
                     result[m][i] = result[m][i].get_cond()
                         | Ins::fun_from_index(invmap[result[m][i].source_index()]);
@@ -542,7 +672,61 @@ fn actualize_solution(program: &Source, puzzle: &Puzzle) -> Source {
     return result;
 }
 
-pub fn encode_program(program: &Source, puzzle: &Puzzle) -> String {
+fn deactualize_solution<
+    const W: usize,
+    const H: usize,
+    const N: usize,
+    const M: usize,
+    P: InsPointer,
+>(
+    program: &Source<N, M>,
+    puzzle: &Puzzle<W, H, N, M, P>,
+) -> Source<N, M> {
+    let mut result = *program;
+    if puzzle.methods != puzzle.actual_methods {
+        let (mapping, _) = method_mapping(puzzle);
+        for m in 0..N {
+            result[mapping[m]] = program[m];
+        }
+        for m in 0..N {
+            for i in 0..M {
+                if result[m][i].is_function() {
+                    result[m][i] = result[m][i].get_cond()
+                        | Ins::fun_from_index(mapping[result[m][i].source_index()]);
+                }
+            }
+        }
+    }
+    result
+}
+
+pub fn encode_program<
+    const W: usize,
+    const H: usize,
+    const N: usize,
+    const M: usize,
+    P: InsPointer,
+>(
+    program: &Source<N, M>,
+    puzzle: &Puzzle<W, H, N, M, P>,
+) -> Result<String, ProgramError> {
+    // the site only reads codes of its five methods of up to ten slots
+    for (m, &length) in puzzle.actual_methods.iter().enumerate() {
+        if m >= STANDARD_METHODS && length > 0 {
+            return Err(ProgramError::Unencodable(format!(
+                "F{} is past the {} methods of the site",
+                m + 1,
+                STANDARD_METHODS
+            )));
+        } else if length > STANDARD_METHOD_LENGTH {
+            return Err(ProgramError::Unencodable(format!(
+                "F{} has {} slots, the site allows {}",
+                m + 1,
+                length,
+                STANDARD_METHOD_LENGTH
+            )));
+        }
+    }
     let solution = actualize_solution(program, puzzle);
     let mut encode_state = StateEncoder {
         output: "".parse().unwrap(),
@@ -551,13 +735,22 @@ pub fn encode_program(program: &Source, puzzle: &Puzzle) -> String {
     };
     // program_length = robozzle.program.length; would be expected to be like this:
     // let program_length = puzzle.functions.iter().filter(|&method| *method != 0).count();
-    let program_length = 5; // but it seems to be like this always.
+    let program_length = STANDARD_METHODS; // but it seems to be like this always.
     encode_state.encode_bits(0, 3); // Version number = 0
     encode_state.encode_bits(program_length, 3);
     for i in 0..program_length {
-        encode_state.encode_bits(puzzle.methods[i], 4);
-        for j in 0..puzzle.methods[i] {
+        let length = puzzle.actual_methods.get(i).copied().unwrap_or(0);
+        encode_state.encode_bits(length, 4);
+        for j in 0..length {
             let ins = solution[i][j];
+            if ins.is_function() && ins.source_index() >= STANDARD_METHODS {
+                return Err(ProgramError::Unencodable(format!(
+                    "F{} calls F{}, the site has {} methods",
+                    i + 1,
+                    ins.source_index() + 1,
+                    STANDARD_METHODS
+                )));
+            }
             encode_state.encode_command(
                 match ins.get_cond() {
                     RED_COND => 'R',
@@ -583,5 +776,80 @@ pub fn encode_program(program: &Source, puzzle: &Puzzle) -> String {
         }
     }
     encode_state.encode_bits(0, 5); // Flush
-    return encode_state.output.clone();
+    return Ok(encode_state.output.clone());
+}
+
+/// Reads a program given either in the plain notation, as the `program=` code
+/// of a robozzle URL or as the whole URL.
+pub fn read_program<
+    const W: usize,
+    const H: usize,
+    const N: usize,
+    const M: usize,
+    P: InsPointer,
+>(
+    text: &str,
+    puzzle: &Puzzle<W, H, N, M, P>,
+) -> Result<Source<N, M>, ProgramError> {
+    let text = text.trim();
+    let code = match text.split_once("program=") {
+        Some((_, code)) => code.split('&').next().unwrap_or(code),
+        None => text,
+    };
+    if code.contains(|c: char| c.is_whitespace() || c == '|' || c == ':') {
+        return parse_program(text, puzzle);
+    }
+    decode_program(code, puzzle).or_else(|err| parse_program(text, puzzle).map_err(|_| err))
+}
+
+/// Decodes the `program=` part of a robozzle URL, the inverse of `encode_program`.
+pub fn decode_program<
+    const W: usize,
+    const H: usize,
+    const N: usize,
+    const M: usize,
+    P: InsPointer,
+>(
+    code: &str,
+    puzzle: &Puzzle<W, H, N, M, P>,
+) -> Result<Source<N, M>, ProgramError> {
+    let mut decode_state = StateDecoder {
+        input: code.chars(),
+        val: 0,
+        bits: 6,
+    };
+    let version = decode_state.decode_bits(3)?;
+    if version != 0 {
+        return Err(ProgramError::UnsupportedVersion(version));
+    }
+    let program_length = decode_state.decode_bits(3)?;
+    let mut solution = Source::halted();
+    for i in 0..program_length.max(N) {
+        let length = if i < program_length {
+            decode_state.decode_bits(4)?
+        } else {
+            0
+        };
+        let expected = puzzle.actual_methods.get(i).copied().unwrap_or(0);
+        if length != expected {
+            return Err(ProgramError::MethodLengthMismatch {
+                method: i,
+                length,
+                expected,
+            });
+        }
+        for j in 0..length {
+            solution[i][j] = decode_state.decode_command(N.min(STANDARD_METHODS))?;
+        }
+    }
+    for i in 0..N {
+        for &ins in solution[i].iter() {
+            if ins.is_function() && puzzle.actual_methods[ins.source_index()] == 0 {
+                return Err(ProgramError::UnavailableMethod {
+                    method: ins.source_index(),
+                });
+            }
+        }
+    }
+    Ok(deactualize_solution(&solution, puzzle))
 }
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt::{Debug, Error, Formatter};
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use super::errors::SolverError;
+
+pub const USERNAME_VARIABLE: &str = "ROBOZZLE_USERNAME";
+pub const PASSWORD_VARIABLE: &str = "ROBOZZLE_PASSWORD";
+/// Environment variable with the path of the credentials file, see
+/// [`FileCredentials`].
+pub const FILE_VARIABLE: &str = "ROBOZZLE_CREDENTIALS";
+
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+pub trait CredentialProvider {
+    /// The credentials, or [`SolverError::MissingCredentials`] when this
+    /// provider has none.
+    fn credentials(&self) -> Result<Credentials, SolverError>;
+}
+
+/// Where providers look up environment variables, the process environment
+/// by default.
+#[derive(Clone, Debug, Default)]
+pub struct Env(Option<HashMap<String, OsString>>);
+
+impl Env {
+    /// Only the given variables, regardless of the process environment.
+    pub fn from_vars<'a>(vars: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        Env(Some(
+            vars.into_iter()
+                .map(|(name, value)| (name.to_string(), OsString::from(value)))
+                .collect(),
+        ))
+    }
+    pub fn var_os(&self, name: &str) -> Option<OsString> {
+        match &self.0 {
+            Some(vars) => vars.get(name).cloned(),
+            None => std::env::var_os(name),
+        }
+    }
+}
+
+/// Reads `ROBOZZLE_USERNAME` and `ROBOZZLE_PASSWORD`.
+#[derive(Clone, Debug, Default)]
+pub struct EnvCredentials {
+    pub env: Env,
+}
+
+impl CredentialProvider for EnvCredentials {
+    fn credentials(&self) -> Result<Credentials, SolverError> {
+        let variable = |name| {
+            self.env
+                .var_os(name)
+                .and_then(|value| value.into_string().ok())
+                .filter(|value| !value.is_empty())
+                .ok_or_else(|| SolverError::MissingCredentials(format!("{} is not set", name)))
+        };
+        Ok(Credentials {
+            username: variable(USERNAME_VARIABLE)?,
+            password: variable(PASSWORD_VARIABLE)?,
+        })
+    }
+}
+
+/// Reads a JSON file of the form `{"username": "...", "password": "..."}`.
+pub struct FileCredentials {
+    pub path: PathBuf,
+}
+
+impl FileCredentials {
+    /// The file named by `ROBOZZLE_CREDENTIALS`, or
+    /// `~/.config/robozzle/credentials.json` when it is unset.
+    pub fn from_env(env: &Env) -> Self {
+        let path = env
+            .var_os(FILE_VARIABLE)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| {
+                let mut path = env.var_os("HOME").map_or_else(PathBuf::new, PathBuf::from);
+                path.extend([".config", "robozzle", "credentials.json"]);
+                path
+            });
+        FileCredentials { path }
+    }
+}
+
+impl CredentialProvider for FileCredentials {
+    fn credentials(&self) -> Result<Credentials, SolverError> {
+        let mut contents = String::new();
+        File::open(&self.path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|err| {
+                SolverError::MissingCredentials(format!(
+                    "unable to read {}: {}",
+                    self.path.display(),
+                    err
+                ))
+            })?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+/// Tries the environment variables first and the credentials file second.
+#[derive(Clone, Debug, Default)]
+pub struct DefaultCredentials {
+    pub env: Env,
+}
+
+impl CredentialProvider for DefaultCredentials {
+    fn credentials(&self) -> Result<Credentials, SolverError> {
+        let env = EnvCredentials {
+            env: self.env.clone(),
+        };
+        env.credentials().or_else(|env_err| {
+            FileCredentials::from_env(&self.env)
+                .credentials()
+                .map_err(|file_err| match (env_err, file_err) {
+                    (
+                        SolverError::MissingCredentials(env_err),
+                        SolverError::MissingCredentials(file_err),
+                    ) => SolverError::MissingCredentials(format!("{}, {}", env_err, file_err)),
+                    (_, file_err) => file_err,
+                })
+        })
+    }
+}
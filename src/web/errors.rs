@@ -1,6 +1,8 @@
 use thirtyfour::fantoccini::error::CmdError;
 use thirtyfour::prelude::*;
 
+use crate::game::notation::ProgramError;
+
 #[derive(Debug)]
 pub enum SolverError {
     Fantoccini(CmdError),
@@ -9,6 +11,17 @@ pub enum SolverError {
     WebDriver(WebDriverError),
     Serde(serde_json::Error),
     NoPuzzleForId,
+    /// A field of the level with id `id` is malformed or out of bounds.
+    InvalidLevel {
+        id: String,
+        field: &'static str,
+        reason: String,
+    },
+    Program(ProgramError),
+    /// The robozzle service could not be reached or answered unexpectedly.
+    Service(String),
+    /// No credentials were configured, the message says where they were looked for.
+    MissingCredentials(String),
 }
 
 impl From<serde_json::Error> for SolverError {
@@ -17,6 +30,12 @@ impl From<serde_json::Error> for SolverError {
     }
 }
 
+impl From<ProgramError> for SolverError {
+    fn from(value: ProgramError) -> Self {
+        SolverError::Program(value)
+    }
+}
+
 impl From<CmdError> for SolverError {
     fn from(err: CmdError) -> Self {
         SolverError::Fantoccini(err)
@@ -0,0 +1,190 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+
+use super::errors::SolverError;
+use super::{read_level_from_path, Level};
+
+/// Where [`get_all_local_levels`](super::get_all_local_levels) keeps the
+/// index of `data/puzzles`.
+pub const INDEX_PATH: &str = "data/puzzles.index.json";
+/// Bumped whenever the serialized form of [`Level`] changes, older indexes
+/// are rebuilt.
+const INDEX_VERSION: u32 = 1;
+
+/// The parsed levels of a puzzle directory by file name, so that listing the
+/// directory only has to parse the files that changed since the index was
+/// built. A file counts as changed when its length or modification time
+/// differ from the ones it was indexed with.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PuzzleIndex {
+    version: u32,
+    entries: HashMap<String, IndexEntry>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct IndexEntry {
+    modified: SystemTime,
+    len: u64,
+    /// `None` for ids without a level, which are stored as `null`.
+    level: Option<Level>,
+}
+
+impl IndexEntry {
+    fn is_fresh(&self, metadata: &Metadata) -> bool {
+        metadata.len() == self.len && metadata.modified().ok() == Some(self.modified)
+    }
+}
+
+/// What [`PuzzleIndex::refresh`] found in the directory.
+#[derive(Debug, Default)]
+pub struct Refresh {
+    /// The levels in directory order.
+    pub levels: Vec<Level>,
+    /// Files that could not be read as a level, including `null` ones, and
+    /// entries of the directory that could not be listed.
+    pub errors: Vec<(PathBuf, SolverError)>,
+    /// Files parsed because they were new or changed.
+    pub parsed: usize,
+    /// Whether the index differs from the one it was loaded as.
+    pub changed: bool,
+}
+
+impl PuzzleIndex {
+    /// The index stored at `path`, or an empty one when it is missing,
+    /// unreadable or from another version.
+    pub fn load(path: &Path) -> PuzzleIndex {
+        fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<PuzzleIndex>(&bytes).ok())
+            .filter(|index| index.version == INDEX_VERSION)
+            .unwrap_or_default()
+    }
+    /// Writes the index to a sibling file first so that readers never see a
+    /// partial index. The file is named after the process, so that processes
+    /// storing at once don't write into each other's file.
+    pub fn store(&self, path: &Path) -> Result<(), SolverError> {
+        let partial = path.with_extension(format!("{}.partial", process::id()));
+        fs::write(
+            &partial,
+            serde_json::to_vec(&PuzzleIndex {
+                version: INDEX_VERSION,
+                entries: self.entries.clone(),
+            })?,
+        )?;
+        fs::rename(partial, path)?;
+        Ok(())
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    /// The indexed level of the file at `path`, `None` unless the entry is
+    /// fresh. The level is [`SolverError::NoPuzzleForId`] for `null` files.
+    pub fn get(&self, path: &Path, metadata: &Metadata) -> Option<Result<Level, SolverError>> {
+        let entry = self.entries.get(path.file_name()?.to_str()?)?;
+        entry
+            .is_fresh(metadata)
+            .then(|| entry.level.clone().ok_or(SolverError::NoPuzzleForId))
+    }
+    /// Reads the level at `path` and indexes it unless it is malformed,
+    /// returning whether the index changed.
+    fn parse(
+        &mut self,
+        name: String,
+        path: &Path,
+        metadata: &Metadata,
+    ) -> (Result<Level, SolverError>, bool) {
+        let level = read_level_from_path(path.to_path_buf());
+        let indexed = match (&level, metadata.modified()) {
+            (Ok(level), Ok(modified)) => (Some(level.clone()), modified),
+            (Err(SolverError::NoPuzzleForId), Ok(modified)) => (None, modified),
+            _ => {
+                let removed = self.entries.remove(&name).is_some();
+                return (level, removed);
+            }
+        };
+        self.entries.insert(
+            name,
+            IndexEntry {
+                modified: indexed.1,
+                len: metadata.len(),
+                level: indexed.0,
+            },
+        );
+        (level, true)
+    }
+    /// Brings the index up to date with `directory`, parsing new and changed
+    /// files and forgetting removed ones.
+    pub fn refresh(&mut self, directory: &Path) -> Result<Refresh, SolverError> {
+        let mut refresh = Refresh {
+            changed: self.version != INDEX_VERSION,
+            ..Refresh::default()
+        };
+        self.version = INDEX_VERSION;
+        let mut seen = HashSet::new();
+        for dir in fs::read_dir(directory)? {
+            let dir = match dir {
+                Ok(dir) => dir,
+                Err(err) => {
+                    refresh.errors.push((directory.to_path_buf(), err.into()));
+                    continue;
+                }
+            };
+            let path = dir.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let name = name.to_string();
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(err) => {
+                    refresh.errors.push((path, err.into()));
+                    continue;
+                }
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            let level = match self.get(&path, &metadata) {
+                Some(level) => level,
+                None => {
+                    let (level, changed) = self.parse(name.clone(), &path, &metadata);
+                    refresh.parsed += 1;
+                    refresh.changed |= changed;
+                    level
+                }
+            };
+            match level {
+                Ok(level) => refresh.levels.push(level),
+                Err(err) => refresh.errors.push((path, err)),
+            }
+            seen.insert(name);
+        }
+        let indexed = self.entries.len();
+        self.entries.retain(|name, _| seen.contains(name));
+        refresh.changed |= self.entries.len() != indexed;
+        Ok(refresh)
+    }
+}
+
+static LOCAL_INDEX: OnceCell<Mutex<PuzzleIndex>> = OnceCell::new();
+
+/// The index of `data/puzzles`, loaded from [`INDEX_PATH`] on first use.
+pub(crate) fn local_index() -> &'static Mutex<PuzzleIndex> {
+    LOCAL_INDEX.get_or_init(|| Mutex::new(PuzzleIndex::load(Path::new(INDEX_PATH))))
+}
+
+/// The index of `data/puzzles` if this process already loaded it, reading a
+/// single level is cheaper than loading the index.
+pub(crate) fn loaded_local_index() -> Option<&'static Mutex<PuzzleIndex>> {
+    LOCAL_INDEX.get()
+}
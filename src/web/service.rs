@@ -0,0 +1,369 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use serde_json::{Map, Value};
+
+use super::credentials::Credentials;
+use super::errors::SolverError;
+use super::{Level, LevelJson};
+
+pub const DEFAULT_BASE_URL: &str = "http://www.robozzle.com";
+/// Environment variable overriding [`DEFAULT_BASE_URL`] in [`ServiceClient::from_env`].
+pub const BASE_URL_VARIABLE: &str = "ROBOZZLE_URL";
+const SERVICE_PATH: &str = "/RobozzleService.svc";
+const SERVICE_NAMESPACE: &str = "http://tempuri.org/";
+const SERVICE_INTERFACE: &str = "IRobozzleService";
+
+/// Blocking client for the SOAP service behind the robozzle web page, speaking
+/// plain HTTP/1.1 so that no browser is needed to fetch levels.
+#[derive(Clone, Debug)]
+pub struct ServiceClient {
+    host: String,
+    port: u16,
+    /// Path prefix of the service, without a trailing slash.
+    path: String,
+    pub timeout: Duration,
+}
+
+impl Default for ServiceClient {
+    fn default() -> Self {
+        ServiceClient::new(DEFAULT_BASE_URL).expect("the default base URL is valid")
+    }
+}
+
+impl ServiceClient {
+    /// Client for the service below `base_url`, e.g. `http://localhost:8080`.
+    /// Only plain `http` is supported.
+    pub fn new(base_url: &str) -> Result<Self, SolverError> {
+        let invalid = || SolverError::Service(format!("invalid base URL '{}'", base_url));
+        let rest = base_url.strip_prefix("http://").ok_or_else(invalid)?;
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(ServiceClient {
+            host: host.to_string(),
+            port,
+            path: path.trim_end_matches('/').to_string(),
+            timeout: Duration::from_secs(30),
+        })
+    }
+
+    /// Client for the URL in the `ROBOZZLE_URL` environment variable, or the
+    /// public service when it is unset.
+    pub fn from_env() -> Result<Self, SolverError> {
+        match std::env::var(BASE_URL_VARIABLE) {
+            Ok(url) => ServiceClient::new(&url),
+            Err(_) => Ok(ServiceClient::default()),
+        }
+    }
+
+    /// The raw `GetLevelResult`, shaped like the JSON stored in `data/puzzles`.
+    /// It is `null` when there is no level with the id.
+    pub fn get_level_json(&self, level_id: u64) -> Result<Value, SolverError> {
+        self.call("GetLevel", &[("levelId", level_id.to_string())])
+    }
+
+    pub fn get_level(&self, level_id: u64) -> Result<Level, SolverError> {
+        let json = self.get_level_json(level_id)?;
+        serde_json::from_value::<Option<LevelJson>>(json)?
+            .ok_or(SolverError::NoPuzzleForId)
+            .and_then(Level::try_from)
+    }
+
+    /// Submits a solution in the `program=` URL encoding, see
+    /// [`encode_program`](super::encode_program). Returns the service's
+    /// `SubmitSolutionResult` as is.
+    pub fn submit_solution(
+        &self,
+        level_id: u64,
+        credentials: &Credentials,
+        solution: &str,
+    ) -> Result<Value, SolverError> {
+        self.call(
+            "SubmitSolution",
+            &[
+                ("levelId", level_id.to_string()),
+                ("userName", credentials.username.clone()),
+                ("password", credentials.password.clone()),
+                ("solution", solution.to_string()),
+            ],
+        )
+    }
+
+    /// Invokes `method` with `arguments` in order and returns its result element
+    /// converted to JSON.
+    pub fn call(&self, method: &str, arguments: &[(&str, String)]) -> Result<Value, SolverError> {
+        let mut body = format!(
+            "<s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\"><s:Body>\
+             <{} xmlns=\"{}\">",
+            method, SERVICE_NAMESPACE
+        );
+        for (name, value) in arguments {
+            body.push_str(&format!("<{0}>{1}</{0}>", name, escape(value)));
+        }
+        body.push_str(&format!("</{}></s:Body></s:Envelope>", method));
+        let action = format!("{}{}/{}", SERVICE_NAMESPACE, SERVICE_INTERFACE, method);
+        let (status, response) = self.post(&body, &action)?;
+
+        let envelope = parse_xml(&response)?;
+        let body = envelope
+            .child("Body")
+            .ok_or_else(|| SolverError::Service("response has no SOAP body".to_string()))?;
+        if let Some(fault) = body.child("Fault") {
+            let reason = fault
+                .child("faultstring")
+                .map_or_else(|| "unknown fault".to_string(), |reason| reason.text.clone());
+            return Err(SolverError::Service(format!(
+                "{} failed: {}",
+                method, reason
+            )));
+        }
+        if status != 200 {
+            return Err(SolverError::Service(format!(
+                "{} failed with HTTP status {}",
+                method, status
+            )));
+        }
+        body.child(&format!("{}Response", method))
+            .and_then(|response| response.child(&format!("{}Result", method)))
+            .map_or(Ok(Value::Null), |result| Ok(result.to_json()))
+    }
+
+    /// Sends an HTTP POST and returns the status code and response body.
+    fn post(&self, body: &str, action: &str) -> Result<(u16, String), SolverError> {
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        write!(
+            stream,
+            "POST {}{} HTTP/1.1\r\n\
+             Host: {}\r\n\
+             Content-Type: text/xml; charset=utf-8\r\n\
+             SOAPAction: \"{}\"\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\r\n{}",
+            self.path,
+            SERVICE_PATH,
+            self.host,
+            action,
+            body.len(),
+            body
+        )?;
+        stream.flush()?;
+        read_response(BufReader::new(stream))
+    }
+}
+
+fn read_response(mut reader: impl BufRead) -> Result<(u16, String), SolverError> {
+    let malformed = |what: &str| SolverError::Service(format!("malformed HTTP response: {}", what));
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| malformed("status line"))?;
+    let mut content_length = None;
+    let mut chunked = false;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(malformed("headers"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = Some(value.parse::<usize>().map_err(|_| malformed(header))?);
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value.eq_ignore_ascii_case("chunked");
+            }
+        }
+    }
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            line.clear();
+            reader.read_line(&mut line)?;
+            let size = line.trim_end().split(';').next().unwrap_or("");
+            let size = usize::from_str_radix(size, 16).map_err(|_| malformed("chunk size"))?;
+            if size == 0 {
+                break;
+            }
+            let start = body.len();
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..])?;
+            line.clear();
+            reader.read_line(&mut line)?;
+        }
+    } else if let Some(length) = content_length {
+        body.resize(length, 0);
+        reader.read_exact(&mut body)?;
+    } else {
+        reader.read_to_end(&mut body)?;
+    }
+    String::from_utf8(body)
+        .map(|body| (status, body))
+        .map_err(|_| malformed("body is not UTF-8"))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape(text: &str) -> Result<String, SolverError> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find(';')
+            .ok_or_else(|| SolverError::Service("unterminated XML entity".to_string()))?;
+        let entity = &rest[start + 1..start + end];
+        result.push(match entity {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse::<u32>()))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32)
+                .ok_or_else(|| SolverError::Service(format!("unknown XML entity '{}'", entity)))?,
+        });
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Just enough XML for SOAP responses: namespace prefixes are dropped and only
+/// the `nil` attribute is kept.
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    nil: bool,
+    text: String,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// Leaves become strings and elements whose children share a name, like
+    /// the rows of `Colors`, become arrays.
+    fn to_json(&self) -> Value {
+        if self.nil {
+            Value::Null
+        } else if self.children.is_empty() {
+            Value::String(self.text.clone())
+        } else if self.children.len() > 1
+            && self
+                .children
+                .iter()
+                .all(|child| child.name == self.children[0].name)
+            || ["string", "int", "boolean"].contains(&self.children[0].name.as_str())
+        {
+            Value::Array(self.children.iter().map(Element::to_json).collect())
+        } else {
+            Value::Object(
+                self.children
+                    .iter()
+                    .map(|child| (child.name.clone(), child.to_json()))
+                    .collect::<Map<_, _>>(),
+            )
+        }
+    }
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit_once(':').map_or(name, |(_, local)| local)
+}
+
+/// Parses the root element of `xml`.
+fn parse_xml(xml: &str) -> Result<Element, SolverError> {
+    let malformed = |what: &str| SolverError::Service(format!("malformed XML: {}", what));
+    let mut stack = vec![Element::default()];
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        let text = &rest[..start];
+        if !text.is_empty() {
+            let parent = stack.last_mut().expect("the document is never popped");
+            parent.text.push_str(&unescape(text)?);
+        }
+        rest = &rest[start..];
+        if let Some(data) = rest.strip_prefix("<![CDATA[") {
+            let end = data
+                .find("]]>")
+                .ok_or_else(|| malformed("unterminated CDATA"))?;
+            let parent = stack.last_mut().expect("the document is never popped");
+            parent.text.push_str(&data[..end]);
+            rest = &data[end + 3..];
+            continue;
+        }
+        let end = rest
+            .find('>')
+            .ok_or_else(|| malformed("unterminated tag"))?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            let element = stack.pop().ok_or_else(|| malformed("unbalanced tags"))?;
+            if element.name != local_name(name.trim()) || stack.is_empty() {
+                return Err(malformed("mismatched closing tag"));
+            }
+            stack
+                .last_mut()
+                .expect("checked above")
+                .children
+                .push(element);
+            continue;
+        }
+        let self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let mut parts = tag.split_whitespace();
+        let name = parts.next().ok_or_else(|| malformed("empty tag"))?;
+        let element = Element {
+            name: local_name(name).to_string(),
+            nil: parts.any(|attribute| {
+                attribute.split_once('=').is_some_and(|(name, value)| {
+                    local_name(name) == "nil" && value.contains("true")
+                })
+            }),
+            ..Element::default()
+        };
+        if self_closing {
+            stack
+                .last_mut()
+                .expect("the document is never popped")
+                .children
+                .push(element);
+        } else {
+            stack.push(element);
+        }
+    }
+    let mut document = stack.pop().ok_or_else(|| malformed("unbalanced tags"))?;
+    if !stack.is_empty() || document.children.len() != 1 {
+        return Err(malformed("expected a single root element"));
+    }
+    Ok(document.children.remove(0))
+}
@@ -1,4 +1,6 @@
 use super::*;
+use crate::game::state::won;
+use crate::game::{MAP_HEIGHT, MAP_WIDTH};
 
 #[test]
 fn parse_json() {
@@ -12,6 +14,162 @@ fn test_get_local_puzzle() {
     get_local_level(100);
 }
 
+#[test]
+fn test_program_code_round_trip() {
+    for (puzzle, solution) in [
+        (PUZZLE_42, PUZZLE_42_SOLUTION),
+        (PUZZLE_536, PUZZLE_536_SOLUTION),
+        (PUZZLE_656, PUZZLE_656_SOLUTION),
+        (PUZZLE_1337, PUZZLE_1337_SOLUTION),
+    ] {
+        let code = encode_program(&solution, &puzzle).unwrap();
+        assert_eq!(decode_program(&code, &puzzle), Ok(solution), "{}", code);
+    }
+}
+
+#[test]
+fn test_program_code_permuted_methods() {
+    let puzzle = Puzzle {
+        methods: [5, 2, 2, 2, 0],
+        actual_methods: [5, 0, 2, 2, 2],
+        ..PUZZLE_42
+    };
+    let code = encode_program(&PUZZLE_42_SOLUTION, &puzzle).unwrap();
+    assert_eq!(decode_program(&code, &puzzle), Ok(PUZZLE_42_SOLUTION));
+    assert!(puzzle.execute(&decode_program(&code, &puzzle).unwrap(), false, won));
+}
+
+#[test]
+fn test_read_program() {
+    let code = encode_program(&PUZZLE_42_SOLUTION, &PUZZLE_42).unwrap();
+    let url = format!(
+        "http://www.robozzle.com/js/play.aspx?puzzle=42&program={}",
+        code
+    );
+    for text in [
+        code.as_str(),
+        url.as_str(),
+        "F1: 2 < 3 < 1 | F2: 3 3 | F3: 4 4 | F4: f f",
+    ] {
+        assert_eq!(
+            read_program(text, &PUZZLE_42),
+            Ok(PUZZLE_42_SOLUTION),
+            "{}",
+            text
+        );
+    }
+    assert!(read_program("F1: x", &PUZZLE_42).is_err());
+}
+
+#[test]
+fn test_program_code_errors() {
+    let code = encode_program(&PUZZLE_42_SOLUTION, &PUZZLE_42).unwrap();
+    assert_eq!(
+        decode_program(&code[..code.len() / 2], &PUZZLE_42),
+        Err(ProgramError::Truncated)
+    );
+    assert_eq!(
+        decode_program(&format!("{}!", &code[..3]), &PUZZLE_42),
+        Err(ProgramError::InvalidCharacter('!'))
+    );
+    let mut versioned = code.clone();
+    versioned.replace_range(0..1, &((code.as_bytes()[0] + 1) as char).to_string());
+    assert_eq!(
+        decode_program(&versioned, &PUZZLE_42),
+        Err(ProgramError::UnsupportedVersion(1))
+    );
+    assert!(matches!(
+        decode_program(&code, &PUZZLE_536),
+        Err(ProgramError::MethodLengthMismatch { .. })
+    ));
+}
+
+fn level_with(edit: impl FnOnce(&mut Value)) -> Result<Level, SolverError> {
+    let mut json: Value = serde_json::from_str(LEVEL_JSON).unwrap();
+    edit(&mut json);
+    Level::try_from(serde_json::from_value::<LevelJson>(json).unwrap())
+}
+
+fn invalid_field(result: Result<Level, SolverError>) -> &'static str {
+    match result {
+        Err(SolverError::InvalidLevel { id, field, .. }) => {
+            assert_eq!(id, "1874");
+            field
+        }
+        other => panic!("expected an invalid level, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_level_validation() {
+    let level = level_with(|_| ()).unwrap();
+    assert_eq!(level.id, 1874);
+    assert_eq!(level.puzzle.actual_methods, [10, 6, 4, 2, 0]);
+
+    assert_eq!(
+        invalid_field(level_with(|json| json["Liked"] = "many".into())),
+        "Liked"
+    );
+    assert_eq!(
+        invalid_field(level_with(|json| json["Featured"] = "yes".into())),
+        "Featured"
+    );
+    assert_eq!(
+        invalid_field(level_with(|json| json["Colors"]
+            .as_array_mut()
+            .unwrap()
+            .truncate(11))),
+        "Colors"
+    );
+    assert_eq!(
+        invalid_field(level_with(|json| json["Items"][4] = "#####*....***".into())),
+        "Items"
+    );
+    assert_eq!(
+        invalid_field(level_with(
+            |json| json["Colors"][2] = "RRRRRXGGGGGRRRRR".into()
+        )),
+        "Colors"
+    );
+    assert_eq!(
+        invalid_field(level_with(|json| json["RobotDir"] = "4".into())),
+        "RobotDir"
+    );
+    assert_eq!(
+        invalid_field(level_with(|json| json["RobotCol"] = "16".into())),
+        "RobotCol"
+    );
+    assert_eq!(
+        invalid_field(level_with(|json| json["RobotRow"] = "-1".into())),
+        "RobotRow"
+    );
+    // row 3, column 1 is a hole
+    assert_eq!(
+        invalid_field(level_with(|json| json["RobotCol"] = "1".into())),
+        "Items"
+    );
+    assert_eq!(
+        invalid_field(level_with(|json| json["SubLengths"][1] = "11".into())),
+        "SubLengths"
+    );
+    assert_eq!(
+        invalid_field(level_with(|json| json["SubLengths"][4] = "ten".into())),
+        "SubLengths"
+    );
+}
+
+#[test]
+fn test_local_levels_are_valid() {
+    for entry in std::fs::read_dir("data/puzzles").unwrap() {
+        let path = entry.unwrap().path();
+        // ids without a level are stored as `null`
+        match read_level_from_path(path.clone()) {
+            Ok(_) | Err(SolverError::NoPuzzleForId) => (),
+            Err(err) => panic!("{}: {:?}", path.display(), err),
+        }
+    }
+}
+
 const LEVEL_JSON: &str = "{
 \"About\": \"Collect starfruit! (See comments for hints - coming soon)\",
 \"AllowedCommands\": \"0\",
@@ -115,3 +273,322 @@ const LEVEL_JSON2: &str = "
   \"Title\": \"Find a way\"
 }
 ";
+
+/// Serves `response` to a single request and hands the request back.
+fn stand_in_server(response: String) -> (String, std::thread::JoinHandle<String>) {
+    use std::io::BufReader;
+    use std::net::TcpListener;
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/robozzle/", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut request = String::new();
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                length = value.trim().parse().unwrap();
+            }
+            request.push_str(&line);
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        request.push_str(&String::from_utf8(body).unwrap());
+        reader.into_inner().write_all(response.as_bytes()).unwrap();
+        request
+    });
+    (url, handle)
+}
+
+fn soap_response(status: &str, body: &str) -> String {
+    let envelope = format!(
+        "<?xml version=\"1.0\"?><s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\">\
+         <s:Body>{}</s:Body></s:Envelope>",
+        body
+    );
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/xml; charset=utf-8\r\nContent-Length: {}\r\n\r\n{}",
+        status,
+        envelope.len(),
+        envelope
+    )
+}
+
+/// The level in the shape the robozzle service sends it.
+fn level_xml(level: &Value) -> String {
+    let mut xml = String::new();
+    for (name, value) in level.as_object().unwrap() {
+        match value {
+            Value::Array(rows) => {
+                xml.push_str(&format!("<a:{} xmlns:b=\"arrays\">", name));
+                for row in rows {
+                    let row = row.as_str().unwrap().replace('&', "&amp;");
+                    xml.push_str(&format!("<b:string>{}</b:string>", row));
+                }
+                xml.push_str(&format!("</a:{}>", name));
+            }
+            Value::String(text) => {
+                let text = text.replace('&', "&amp;").replace('<', "&lt;");
+                xml.push_str(&format!("<a:{0}>{1}</a:{0}>", name, text));
+            }
+            _ => xml.push_str(&format!("<a:{} i:nil=\"true\"/>", name)),
+        }
+    }
+    format!(
+        "<GetLevelResponse xmlns=\"http://tempuri.org/\"><GetLevelResult xmlns:a=\"data\" \
+         xmlns:i=\"instance\">{}</GetLevelResult></GetLevelResponse>",
+        xml
+    )
+}
+
+fn credentials(password: &str) -> credentials::Credentials {
+    credentials::Credentials {
+        username: "name".to_string(),
+        password: password.to_string(),
+    }
+}
+
+#[test]
+fn test_service_get_level() {
+    let stored: Value =
+        serde_json::from_str(&std::fs::read_to_string("data/puzzles/23").unwrap()).unwrap();
+    let (url, server) = stand_in_server(soap_response("200 OK", &level_xml(&stored)));
+    let client = service::ServiceClient::new(&url).unwrap();
+    let level = client.get_level(23).unwrap();
+    let request = server.join().unwrap();
+    assert!(request.starts_with("POST /robozzle/RobozzleService.svc HTTP/1.1\r\n"));
+    assert!(request.contains("SOAPAction: \"http://tempuri.org/IRobozzleService/GetLevel\""));
+    assert!(request.contains("<levelId>23</levelId>"));
+
+    let local = get_local_level(23).unwrap();
+    assert_eq!(level.id, local.id);
+    assert_eq!(level.title, local.title);
+    assert_eq!(level.about, local.about);
+    assert_eq!(level.submitted_by, local.submitted_by);
+    assert_eq!(level.puzzle.board, local.puzzle.board);
+    assert_eq!(level.puzzle.methods, local.puzzle.methods);
+}
+
+#[test]
+fn test_service_missing_level_and_faults() {
+    let missing = "<GetLevelResponse xmlns=\"http://tempuri.org/\">\
+                   <GetLevelResult i:nil=\"true\" xmlns:i=\"instance\"/></GetLevelResponse>";
+    let (url, server) = stand_in_server(soap_response("200 OK", missing));
+    let client = service::ServiceClient::new(&url).unwrap();
+    assert!(matches!(
+        client.get_level(0),
+        Err(SolverError::NoPuzzleForId)
+    ));
+    server.join().unwrap();
+
+    let fault = "<s:Fault><faultcode>s:Client</faultcode>\
+                 <faultstring>bad &amp; wrong</faultstring></s:Fault>";
+    let (url, server) = stand_in_server(soap_response("500 Internal Server Error", fault));
+    let client = service::ServiceClient::new(&url).unwrap();
+    match client.submit_solution(23, &credentials("secret <3"), "_a") {
+        Err(SolverError::Service(message)) => assert!(message.contains("bad & wrong")),
+        other => panic!("expected a fault, got {:?}", other),
+    }
+    let request = server.join().unwrap();
+    assert!(request.contains("<password>secret &lt;3</password>"));
+    assert!(request.contains("<solution>_a</solution>"));
+
+    assert!(service::ServiceClient::new("https://www.robozzle.com").is_err());
+    assert!(service::ServiceClient::new("http://:80").is_err());
+}
+
+#[test]
+fn test_service_chunked_response() {
+    let body = "<s:Envelope xmlns:s=\"soap\"><s:Body><SubmitSolutionResponse>\
+                <SubmitSolutionResult>ok</SubmitSolutionResult></SubmitSolutionResponse>\
+                </s:Body></s:Envelope>";
+    let (first, second) = body.split_at(40);
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+        first.len(),
+        first,
+        second.len(),
+        second
+    );
+    let (url, server) = stand_in_server(response);
+    let client = service::ServiceClient::new(&url).unwrap();
+    assert_eq!(
+        client
+            .submit_solution(23, &credentials("secret"), "_a")
+            .unwrap(),
+        Value::String("ok".to_string())
+    );
+    server.join().unwrap();
+}
+
+#[test]
+fn test_credential_providers() {
+    use credentials::*;
+    let path = std::env::temp_dir().join(format!("robozzle-credentials-{}", std::process::id()));
+    std::fs::write(&path, r#"{"username": "someone", "password": "hunter2"}"#).unwrap();
+    let file_name = path.to_str().unwrap();
+    let expected = Credentials {
+        username: "someone".to_string(),
+        password: "hunter2".to_string(),
+    };
+    let file = FileCredentials { path: path.clone() };
+    assert_eq!(file.credentials().unwrap(), expected);
+    assert!(!format!("{:?}", expected).contains("hunter2"));
+
+    let env = Env::from_vars([(PASSWORD_VARIABLE, "hunter2")]);
+    assert!(matches!(
+        EnvCredentials { env }.credentials(),
+        Err(SolverError::MissingCredentials(message)) if message.contains(USERNAME_VARIABLE)
+    ));
+    let env = Env::from_vars([(PASSWORD_VARIABLE, "hunter2"), (FILE_VARIABLE, file_name)]);
+    assert_eq!(DefaultCredentials { env }.credentials().unwrap(), expected);
+    let env = Env::from_vars([
+        (USERNAME_VARIABLE, "someone else"),
+        (PASSWORD_VARIABLE, "hunter2"),
+        (FILE_VARIABLE, file_name),
+    ]);
+    assert_eq!(
+        DefaultCredentials { env }.credentials().unwrap().username,
+        "someone else"
+    );
+
+    std::fs::remove_file(&path).unwrap();
+    let env = Env::from_vars([(FILE_VARIABLE, file_name)]);
+    match (DefaultCredentials { env }).credentials() {
+        Err(SolverError::MissingCredentials(message)) => {
+            assert!(message.contains(USERNAME_VARIABLE));
+            assert!(message.contains(file_name));
+        }
+        other => panic!("expected missing credentials, got {:?}", other),
+    }
+    let home = FileCredentials::from_env(&Env::from_vars([("HOME", "/home/someone")]));
+    assert_eq!(
+        home.path,
+        PathBuf::from("/home/someone/.config/robozzle/credentials.json")
+    );
+}
+
+#[test]
+fn test_custom_puzzle_size() {
+    let mut json: Value = serde_json::from_str(LEVEL_JSON).unwrap();
+    for field in ["Colors", "Items"] {
+        let rows = json[field].as_array_mut().unwrap();
+        for row in rows.iter_mut() {
+            *row = format!("{}####", row.as_str().unwrap()).into();
+        }
+        rows.push("#".repeat(20).into());
+    }
+    let text = json.to_string();
+    let custom = custom_puzzle_from_string::<22, 15>(&text).unwrap();
+    let standard = puzzle_from_string(LEVEL_JSON);
+    assert_eq!(custom.stars, standard.stars);
+    assert_eq!(custom.methods, standard.methods);
+    assert_eq!(
+        (custom.board.x, custom.board.y),
+        (standard.board.x, standard.board.y)
+    );
+    for (x, y) in Map::<MAP_WIDTH, MAP_HEIGHT>::coordinates() {
+        assert_eq!(custom.board.map.0[y][x], standard.board.map.0[y][x]);
+    }
+    assert!(matches!(
+        custom_puzzle_from_string::<MAP_WIDTH, MAP_HEIGHT>(&text),
+        Err(SolverError::InvalidLevel {
+            field: "Colors",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_wide_program_code() {
+    // the site reads five methods of up to ten slots
+    let board = PUZZLE_42.board;
+    let methods = [3, 16, 1, 2, 1, 1, 0, 0, 2];
+    let puzzle = make_puzzle::<MAP_WIDTH, MAP_HEIGHT, 9, 16, WideInsPtr>(board, methods, [true; 3]);
+    let text = "F1: f R7 G2 | F2: f f < > f f < > f f < > f f < R4 | F3: 7 2 | F4: Bb G< \
+                | F5: < | F6: > | F7: 3";
+    let program = parse_program(text, &puzzle).unwrap();
+    assert!(matches!(
+        encode_program(&program, &puzzle),
+        Err(ProgramError::Unencodable(_))
+    ));
+
+    // smaller programs are written with the site's five methods
+    let small = make_puzzle::<MAP_WIDTH, MAP_HEIGHT, 3, 10, InsPtr>(board, [3, 2, 1], [true; 3]);
+    let standard: Puzzle = make_puzzle(board, [3, 2, 1, 0, 0], [true; 3]);
+    let text = "F1: f R2 G3 | F2: f < | F3: >";
+    let program = parse_program(text, &small).unwrap();
+    let code = encode_program(&program, &small).unwrap();
+    assert_eq!(
+        code,
+        encode_program(&parse_program(text, &standard).unwrap(), &standard).unwrap()
+    );
+    assert_eq!(decode_program(&code, &small), Ok(program));
+    assert_eq!(read_program(&code, &small), Ok(program));
+}
+
+#[test]
+fn test_puzzle_index() {
+    use index::PuzzleIndex;
+    let directory = std::env::temp_dir().join(format!("puzzle-index-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let level_json = std::fs::read_to_string("data/puzzles/42").unwrap();
+    std::fs::write(directory.join("42"), &level_json).unwrap();
+    std::fs::write(directory.join("64"), "null").unwrap();
+    std::fs::write(directory.join("65"), "{").unwrap();
+
+    let mut index = PuzzleIndex::default();
+    let refresh = index.refresh(&directory).unwrap();
+    assert_eq!((refresh.levels.len(), refresh.errors.len()), (1, 2));
+    assert_eq!((refresh.parsed, refresh.changed), (3, true));
+    assert_eq!(index.len(), 2);
+    let path = directory.join("42");
+    let level = index.get(&path, &std::fs::metadata(&path).unwrap());
+    assert_eq!(level.unwrap().unwrap().title, "The powers");
+    let path = directory.join("64");
+    assert!(matches!(
+        index.get(&path, &std::fs::metadata(&path).unwrap()),
+        Some(Err(SolverError::NoPuzzleForId))
+    ));
+
+    // only the malformed file is read again
+    let stored = directory.join("index.json");
+    index.store(&stored).unwrap();
+    let mut index = PuzzleIndex::load(&stored);
+    std::fs::remove_file(&stored).unwrap();
+    let refresh = index.refresh(&directory).unwrap();
+    assert_eq!((refresh.parsed, refresh.changed), (1, false));
+    assert_eq!(refresh.levels[0].puzzle.stars, PUZZLE_42.stars);
+
+    std::fs::write(
+        directory.join("42"),
+        // a different length, the modification time may not have changed
+        level_json.replace("The powers", "The powers 2"),
+    )
+    .unwrap();
+    std::fs::remove_file(directory.join("64")).unwrap();
+    let refresh = index.refresh(&directory).unwrap();
+    assert_eq!((refresh.parsed, refresh.changed), (2, true));
+    assert_eq!(refresh.levels[0].title, "The powers 2");
+    assert_eq!(index.len(), 1);
+    assert!(PuzzleIndex::load(&directory.join("missing")).is_empty());
+
+    // an entry without metadata is reported along with the malformed file
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(directory.join("gone"), directory.join("66")).unwrap();
+        let refresh = index.refresh(&directory).unwrap();
+        assert_eq!((refresh.levels.len(), refresh.errors.len()), (1, 2));
+        assert!(refresh
+            .errors
+            .iter()
+            .any(|(path, err)| path.ends_with("66") && matches!(err, SolverError::IOError(_))));
+    }
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}